@@ -0,0 +1,206 @@
+//! Blog sections
+//!
+//! A section whose `.menu_meta` sets `"listing": "blog"` lists its pages newest first by `ContentMeta.published`,
+//! falling back to file times for posts without one, instead of by weight.  The section's own `index` page is the
+//! listing page and isn't a post, and subdirectories aren't part of the blog, nor are drafts and scheduled posts.
+//! `page_size` posts go on each listing page, numbered from 1.  The archive groups posts by year and month, newest
+//! first, and `post_links` gives a post its neighbours, previous being the older one and next the newer.
+use chrono::{DateTime, Datelike, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::dates::page_date;
+use crate::error::{N4Error, N4Result};
+use crate::web_path::WebPath;
+use crate::{assemble_page, content_location, MenuItemMeta, PageContent, PageSource, Site};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListingMode {
+    /// Lowest ContentMeta.weight first
+    #[default]
+    Weight,
+    /// Newest first, with pagination, archives and post links
+    Blog,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PostSummary {
+    pub web_path: WebPath,
+    /// ContentMeta.path, or the web path when that isn't set
+    pub location: String,
+    pub title: String,
+    pub description: String,
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlogPage {
+    /// 1 based
+    pub number: usize,
+    pub total_pages: usize,
+    pub total_posts: usize,
+    pub posts: Vec<PageContent>,
+    /// Page numbers for the newer and older listing pages
+    pub previous_page: Option<usize>,
+    pub next_page: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArchiveYear {
+    pub year: i32,
+    pub count: usize,
+    pub months: Vec<ArchiveMonth>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArchiveMonth {
+    pub year: i32,
+    /// 1 to 12
+    pub month: u32,
+    pub posts: Vec<PostSummary>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PostLinks {
+    /// The next older post
+    pub previous: Option<PostSummary>,
+    /// The next newer post
+    pub next: Option<PostSummary>,
+}
+
+impl Site {
+    /// Summaries of a blog section's posts, newest first
+    pub fn blog_posts(&self, section: &str) -> N4Result<Vec<PostSummary>> {
+        let section = WebPath::parse(section)?;
+        self.blog_section_meta(&section)?;
+        let mut posts: Vec<PostSummary> = Vec::new();
+        let pages = self.wiki_pages();
+        for web_path in self.content_items(&self.generate_content_state()?)? {
+            let is_post = web_path.parent() == section
+                && web_path.file_name() != Some("index")
+                && !web_path.is_hidden();
+            if !is_post {
+                continue;
+            }
+            let page = match pages.page_parts(&web_path) {
+                Ok(page) if self.config().is_listed(&page.meta) => page,
+                Ok(_) => continue,
+                Err(why) => {
+                    println!("Blog failure. {}", why);
+                    continue;
+                }
+            };
+            posts.push(PostSummary {
+                location: content_location(&web_path, &page.meta),
+                title: page.meta.title.clone(),
+                description: page.meta.description.clone(),
+                date: page_date(&page),
+                web_path,
+            });
+        }
+        posts.sort_by(|a, b| {
+            b.date
+                .cmp(&a.date)
+                .then_with(|| a.web_path.cmp(&b.web_path))
+        });
+        Ok(posts)
+    }
+
+    /// One listing page of a blog section, NotFound past the last page
+    pub fn blog_page(&self, section: &str, number: usize) -> N4Result<BlogPage> {
+        let page_size = self
+            .blog_section_meta(&WebPath::parse(section)?)?
+            .page_size
+            .max(1);
+        let posts = self.blog_posts(section)?;
+        let pages = self.wiki_pages();
+        let total_posts = posts.len();
+        let total_pages = total_posts.div_ceil(page_size).max(1);
+        if number == 0 || number > total_pages {
+            return Err(N4Error::NotFound(format!("{} page {}", section, number)));
+        }
+        let posts = posts
+            .into_iter()
+            .skip((number - 1) * page_size)
+            .take(page_size)
+            .map(|x| assemble_page(&pages, x.web_path, &mut Vec::new()))
+            .collect::<N4Result<Vec<PageContent>>>()?;
+        Ok(BlogPage {
+            number,
+            total_pages,
+            total_posts,
+            posts,
+            previous_page: if number > 1 { Some(number - 1) } else { None },
+            next_page: if number < total_pages {
+                Some(number + 1)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// A blog section's posts by year and then month, newest first
+    pub fn blog_archive(&self, section: &str) -> N4Result<Vec<ArchiveYear>> {
+        let mut years: Vec<ArchiveYear> = Vec::new();
+        for post in self.blog_posts(section)? {
+            let (year, month) = (post.date.year(), post.date.month());
+            // Posts are newest first, so a post is either in the latest group or starts a new one
+            match years.last_mut() {
+                Some(archive_year) if archive_year.year == year => {
+                    archive_year.count += 1;
+                    match archive_year.months.last_mut() {
+                        Some(archive_month) if archive_month.month == month => {
+                            archive_month.posts.push(post)
+                        }
+                        _ => archive_year.months.push(ArchiveMonth {
+                            year,
+                            month,
+                            posts: vec![post],
+                        }),
+                    }
+                }
+                _ => years.push(ArchiveYear {
+                    year,
+                    count: 1,
+                    months: vec![ArchiveMonth {
+                        year,
+                        month,
+                        posts: vec![post],
+                    }],
+                }),
+            }
+        }
+        Ok(years)
+    }
+
+    /// The older and newer neighbours of a post, both None for pages that aren't in a blog section
+    pub fn post_links(&self, post: &str) -> N4Result<PostLinks> {
+        let post = WebPath::parse(post)?;
+        let section = post.parent();
+        if self.section_meta_of(&section)?.listing != ListingMode::Blog {
+            return Ok(PostLinks::default());
+        }
+        let posts = self.blog_posts(&section.to_string())?;
+        let position = match posts.iter().position(|x| x.web_path == post) {
+            Some(val) => val,
+            None => return Ok(PostLinks::default()),
+        };
+        Ok(PostLinks {
+            previous: posts.get(position + 1).cloned(),
+            next: position.checked_sub(1).and_then(|x| posts.get(x)).cloned(),
+        })
+    }
+
+    // The section's meta when it's in blog listing mode
+    fn blog_section_meta(&self, section: &WebPath) -> N4Result<MenuItemMeta> {
+        let meta = self.section_meta_of(section)?;
+        if meta.listing == ListingMode::Blog {
+            Ok(meta)
+        } else {
+            Err(N4Error::InvalidConfig(format!(
+                "Section {} isn't in blog listing mode",
+                section
+            )))
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! In memory content index
+//!
+//! Walks the content tree once and keeps the parsed ContentMeta, section MenuItemMeta and rendered markdown for every
+//! page, so serving a page doesn't go back to the filesystem.  A file watcher (inotify, notify, polling, whatever the
+//! server has) feeds ContentEvents into `ContentIndex::apply` and only the touched entries are reread.
+//!
+//! A page that can't be read, bad meta, front matter or a file without read permission say, is logged and left out of
+//! the index the same way the sitemap skips it, so one broken file doesn't keep the server from starting.  Only
+//! failures walking the tree itself are errors.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{N4Error, N4Result};
+use crate::file_tree::{dir_subtree, get_file_metadata, DirTree};
+use crate::web_path::WebPath;
+use crate::wiki::WikiIndex;
+use crate::{
+    add_menu_metadata, assemble_content_list, assemble_page, MenuItem, MenuItemMeta, PageContent,
+    PageSource, Site, SiteConfig, SiteMapEntry,
+};
+
+/// A change on disk, paths are absolute local paths as a watcher reports them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentEvent {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+#[derive(Debug, Clone)]
+pub struct ContentIndex {
+    site: Site,
+    tree: DirTree,
+    // Pages without their content_list pages, those are assembled from here at read time so they're never stale
+    pages: HashMap<WebPath, PageContent>,
+    // Section meta keyed by the local directory path, same key the DirTree gives us
+    sections: HashMap<PathBuf, MenuItemMeta>,
+    // Titles and paths of the cached pages for their wiki links
+    wiki: Arc<WikiIndex>,
+}
+
+impl ContentIndex {
+    /// Full scan of the site's content tree
+    pub fn build(site: Site) -> N4Result<Self> {
+        let tree = site.generate_content_state()?;
+        let mut index = ContentIndex {
+            site,
+            tree: DirTree::default(),
+            pages: HashMap::new(),
+            sections: HashMap::new(),
+            wiki: Arc::new(WikiIndex::default()),
+        };
+        index.index_dir(&tree)?;
+        index.tree = tree;
+        index.relink();
+        Ok(index)
+    }
+
+    pub fn site(&self) -> &Site {
+        &self.site
+    }
+
+    /// The cached DirTree, what Site::generate_content_state would return without the walk
+    pub fn tree(&self) -> &DirTree {
+        &self.tree
+    }
+
+    /// The cached page without its content_list pages
+    pub fn page(&self, web_path: &WebPath) -> Option<&PageContent> {
+        self.pages.get(web_path)
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = (&WebPath, &PageContent)> {
+        self.pages.iter()
+    }
+
+    /// Same as Site::read_single_page, served from the cache
+    pub fn read_single_page(&self, this_path: &str) -> N4Result<PageContent> {
+        assemble_page(self, WebPath::parse(this_path)?, &mut Vec::new())
+    }
+
+    /// Same as Site::read_content_list, served from the cache
+    pub fn read_content_list(&self, list_o_content: &[String]) -> N4Result<Vec<PageContent>> {
+        assemble_content_list(self, list_o_content, &mut Vec::new())
+    }
+
+    /// Same as Site::tree_to_menus over the cached tree and section meta
+    pub fn menus(&self) -> N4Result<HashMap<String, MenuItem>> {
+        self.site
+            .menus_from_tree(&self.tree, self, &|dir: &DirTree| {
+                Ok(self
+                    .sections
+                    .get(Path::new(&dir.absolute_path))
+                    .cloned()
+                    .unwrap_or_default())
+            })
+    }
+
+    /// Same as Site::generate_sitemap from the cached pages and section meta, nothing is read from disk
+    pub fn generate_sitemap(&self) -> N4Result<Vec<SiteMapEntry>> {
+        let mut web_paths: Vec<&WebPath> = self.pages.keys().filter(|x| !x.is_hidden()).collect();
+        web_paths.sort();
+        let mut entries: Vec<SiteMapEntry> = Vec::new();
+        for web_path in web_paths {
+            let page = &self.pages[web_path];
+            // A format the page doesn't have is left at the epoch
+            let mut lastmod = page.markdown.modified;
+            if let Some(html) = &page.html {
+                lastmod = lastmod.max(html.modified);
+            }
+            if let Some(json) = &page.json {
+                lastmod = lastmod.max(json.modified);
+            }
+            let section_metas = self.section_metas(web_path);
+            if let Some(entry) =
+                self.site
+                    .sitemap_entry_from(web_path, &page.meta, lastmod, &section_metas)
+            {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Same as Site::render_page with the page, menus and section meta from the cache
+    pub fn render_page(&self, this_path: &str) -> N4Result<String> {
+        let page = self.read_single_page(this_path)?;
+        let sections = self.section_metas(&page.web_path);
+        self.site
+            .templates()?
+            .render_page(&page, &self.menus()?, &sections, &self.site)
+    }
+
+    // Cached section metas from the content's own directory up to the top, like Site::ancestor_section_metas
+    fn section_metas(&self, web_path: &WebPath) -> Vec<MenuItemMeta> {
+        let root = PathBuf::from(self.site.local_path());
+        let mut metas: Vec<MenuItemMeta> = Vec::new();
+        let mut section = web_path.parent();
+        while !section.is_root() {
+            if let Some(meta) = self.sections.get(&section.to_local(&root)) {
+                metas.push(meta.clone());
+            }
+            section = section.parent();
+        }
+        metas
+    }
+
+    /// Brings the index up to date with a change on disk.  Only the entries for the changed path are reread, paths
+    /// outside of the content root are ignored.  If that fails part way the whole index is rebuilt rather than left
+    /// half updated, and only a failed rebuild is an Err.
+    pub fn apply(&mut self, event: ContentEvent) -> N4Result<()> {
+        let result = match event {
+            ContentEvent::Created(path) | ContentEvent::Modified(path) => self.refresh(&path),
+            ContentEvent::Removed(path) => self.refresh(&path),
+            ContentEvent::Renamed { from, to } => {
+                self.refresh(&from).and_then(|_| self.refresh(&to))
+            }
+        };
+        if let Err(why) = result {
+            println!("Content index failure. {}", why);
+            return self.rebuild();
+        }
+        self.relink();
+        Ok(())
+    }
+
+    // A page's title or path changing can change where any other page's wiki links go, so when the index moves
+    // every page with wiki links is rendered again against it.  Pages that aren't listed stay out like they do from
+    // Site::wiki_index.
+    fn relink(&mut self) {
+        let mut web_paths: Vec<&WebPath> = self.pages.keys().collect();
+        web_paths.sort();
+        let mut wiki = WikiIndex::default();
+        for web_path in web_paths {
+            let meta = &self.pages[web_path].meta;
+            if self.site.config().is_listed(meta) {
+                wiki.insert(web_path, meta);
+            }
+        }
+        if wiki == *self.wiki {
+            return;
+        }
+        self.wiki = Arc::new(wiki);
+        for page in self.pages.values_mut() {
+            if page.markdown.wiki.is_some() {
+                page.markdown.wiki = Some(self.wiki.clone());
+                page.markdown.rendered = None;
+                page.markdown.render();
+            }
+        }
+    }
+
+    // Every event comes down to "make the index match what's on disk now for this path"
+    fn refresh(&mut self, path: &Path) -> N4Result<()> {
+        let root = PathBuf::from(self.site.local_path());
+        let relative = match path.strip_prefix(&root) {
+            Ok(val) => val,
+            Err(_) => return Ok(()),
+        };
+        let parent = match path.parent() {
+            Some(val) if relative.parent().is_some() => val.to_path_buf(),
+            // The root itself changed, nothing incremental about that
+            _ => return self.rebuild(),
+        };
+        let stem = match path.file_stem() {
+            Some(val) => val.to_string_lossy().to_string(),
+            None => return Ok(()),
+        };
+        if self.tree_node(&parent).is_none() {
+            // Events can arrive out of order for nested creates, a directory we haven't seen yet
+            return self.rebuild();
+        }
+
+        if path.is_dir() {
+            self.remove_dir(path, &stem)?;
+            let parent_relative = self.tree_node(&parent).map(|x| x.relative_path.clone());
+            let subtree = dir_subtree(
+                &path.to_string_lossy(),
+                &parent_relative.unwrap_or_default(),
+                &root,
+            )?;
+            self.index_dir(&subtree)?;
+            self.index_section(&subtree)?;
+            if let Some(node) = self.tree_node_mut(&parent) {
+                node.directories.insert(stem, subtree);
+            }
+            return Ok(());
+        }
+        let was_dir = self
+            .tree_node(path)
+            .is_some_and(|node| Path::new(&node.absolute_path) == path);
+        if !path.exists() && was_dir {
+            return self.remove_dir(path, &stem);
+        }
+
+        self.refresh_tree_file(&parent, path, &stem)?;
+        if path.extension().is_some_and(|x| x == "menu_meta") {
+            self.refresh_section(&parent.join(&stem))
+        } else {
+            self.refresh_page(&self.web_path_of(&parent)?.join(&stem)?)
+        }
+    }
+
+    fn rebuild(&mut self) -> N4Result<()> {
+        *self = ContentIndex::build(self.site.clone())?;
+        Ok(())
+    }
+
+    // Pages and sections for a directory and everything below it
+    fn index_dir(&mut self, dir: &DirTree) -> N4Result<()> {
+        let dir_web_path = self.web_path_of(Path::new(&dir.absolute_path))?;
+        for stem in dir.files.keys() {
+            self.refresh_page(&dir_web_path.join(stem)?)?;
+        }
+        for subdir in dir.directories.values() {
+            self.index_section(subdir)?;
+            self.index_dir(subdir)?;
+        }
+        Ok(())
+    }
+
+    fn index_section(&mut self, dir: &DirTree) -> N4Result<()> {
+        let meta = section_meta(Path::new(&dir.absolute_path))?;
+        self.sections
+            .insert(PathBuf::from(&dir.absolute_path), meta);
+        Ok(())
+    }
+
+    // A page that can't be read, unreadable files included, is dropped and logged
+    fn refresh_page(&mut self, web_path: &WebPath) -> N4Result<()> {
+        if !self.site.has_content(web_path) {
+            self.pages.remove(web_path);
+            return Ok(());
+        }
+        let wiki = self.wiki.clone();
+        match self.site.read_page_parts(web_path, &|| Ok(wiki.clone())) {
+            Ok(mut page) => {
+                page.markdown.render();
+                self.pages.insert(web_path.clone(), page);
+            }
+            Err(why) => {
+                println!("Content index failure. {}", why);
+                self.pages.remove(web_path);
+            }
+        }
+        Ok(())
+    }
+
+    // A .menu_meta changed, the section and every cached page directly in it get the new meta.  The pages are
+    // reread rather than patched since the section's markdown options change how they render.
+    fn refresh_section(&mut self, dir: &Path) -> N4Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        self.sections.insert(dir.to_path_buf(), section_meta(dir)?);
+        let section_web_path = self.web_path_of(dir)?;
+        let in_section: Vec<WebPath> = self
+            .pages
+            .keys()
+            .filter(|x| x.parent() == section_web_path)
+            .cloned()
+            .collect();
+        for web_path in in_section {
+            self.refresh_page(&web_path)?;
+        }
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path, stem: &str) -> N4Result<()> {
+        let dir_web_path = self.web_path_of(path)?;
+        self.pages
+            .retain(|web_path, _| !web_path.starts_with(&dir_web_path));
+        self.sections.retain(|dir, _| !dir.starts_with(path));
+        if let Some(parent) = path.parent() {
+            if let Some(node) = self.tree_node_mut(parent) {
+                node.directories.remove(stem);
+            }
+        }
+        Ok(())
+    }
+
+    // DirTree keys files by stem, so an entry only goes away once nothing with that stem is left in the directory
+    fn refresh_tree_file(&mut self, parent: &Path, path: &Path, stem: &str) -> N4Result<()> {
+        let file_meta = if path.exists() {
+            Some(get_file_metadata(path.to_path_buf())?)
+        } else {
+            sibling_with_stem(parent, stem)
+                .map(get_file_metadata)
+                .transpose()?
+        };
+        if let Some(node) = self.tree_node_mut(parent) {
+            match file_meta {
+                Some(val) => node.files.insert(stem.to_string(), val),
+                None => node.files.remove(stem),
+            };
+        }
+        Ok(())
+    }
+
+    fn web_path_of(&self, local: &Path) -> N4Result<WebPath> {
+        match local.strip_prefix(self.site.local_path()) {
+            Ok(val) => WebPath::parse(&val.to_string_lossy()),
+            Err(_) => Err(N4Error::PathEscape(local.to_string_lossy().to_string())),
+        }
+    }
+
+    fn tree_node(&self, dir: &Path) -> Option<&DirTree> {
+        let relative = dir.strip_prefix(self.site.local_path()).ok()?;
+        let mut node = &self.tree;
+        for component in relative.iter() {
+            node = node.directories.get(&dir_key(component))?;
+        }
+        Some(node)
+    }
+
+    fn tree_node_mut(&mut self, dir: &Path) -> Option<&mut DirTree> {
+        let relative = dir.strip_prefix(self.site.local_path()).ok()?.to_path_buf();
+        let mut node = &mut self.tree;
+        for component in relative.iter() {
+            node = node.directories.get_mut(&dir_key(component))?;
+        }
+        Some(node)
+    }
+}
+
+impl PageSource for ContentIndex {
+    fn site_config(&self) -> &SiteConfig {
+        self.site.config()
+    }
+
+    fn has_content(&self, web_path: &WebPath) -> bool {
+        self.pages.contains_key(web_path)
+    }
+
+    fn page_parts(&self, web_path: &WebPath) -> N4Result<PageContent> {
+        match self.pages.get(web_path) {
+            Some(page) => Ok(page.clone()),
+            None => Err(N4Error::NotFound(web_path.to_string())),
+        }
+    }
+}
+
+// A bad .menu_meta falls back to the defaults, the pages in the section fail on it and get logged on their own
+fn section_meta(dir: &Path) -> N4Result<MenuItemMeta> {
+    match add_menu_metadata(&dir.to_string_lossy()) {
+        Ok(val) => Ok(val),
+        Err(why @ N4Error::Io { .. }) => Err(why),
+        Err(why) => {
+            println!("Content index failure. {}", why);
+            Ok(MenuItemMeta::default())
+        }
+    }
+}
+
+// dir_to_tree keys directories by file stem, match it
+fn dir_key(component: &std::ffi::OsStr) -> String {
+    Path::new(component)
+        .file_stem()
+        .unwrap_or(component)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn sibling_with_stem(dir: &Path, stem: &str) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .find(|x| x.is_file() && x.file_stem().is_some_and(|val| val == stem))
+}
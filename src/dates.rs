@@ -0,0 +1,100 @@
+//! Content dates
+//!
+//! Dates in meta files and front matter can be full RFC 3339 timestamps or just `2024-03-01`, with or without a
+//! time after it, and are read as UTC when there's no offset.  TOML front matter dates work too.
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+
+use crate::{unix_time_to_iso, PageContent};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDate {
+    Text(String),
+    // How a TOML datetime comes through serde_json::Value
+    Toml {
+        #[serde(rename = "$__toml_private_datetime")]
+        value: String,
+    },
+}
+
+/// A date from a meta file, None when it isn't one of the accepted forms
+pub fn parse_date(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(val) = DateTime::parse_from_rfc3339(raw) {
+        return Some(val.with_timezone(&Utc));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(val) = NaiveDateTime::parse_from_str(raw, format) {
+            return Some(val.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .and_then(|x| x.and_hms_opt(0, 0, 0))
+        .map(|x| x.and_utc())
+}
+
+/// Optional date field, unset or null is None and anything that isn't a date an error
+pub(crate) fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = match Option::<RawDate>::deserialize(deserializer)? {
+        Some(RawDate::Text(val)) | Some(RawDate::Toml { value: val }) => val,
+        None => return Ok(None),
+    };
+    match parse_date(&raw) {
+        Some(val) => Ok(Some(val)),
+        None => Err(serde::de::Error::custom(format!(
+            "{} isn't a date, use 2024-03-01 or an RFC 3339 timestamp",
+            raw
+        ))),
+    }
+}
+
+/// When a page was published, ContentMeta.published or else the newest modified time of its files
+pub fn page_date(page: &PageContent) -> DateTime<Utc> {
+    if let Some(published) = page.meta.published {
+        return published;
+    }
+    let mut date = unix_time_to_iso(0.0);
+    if !page.markdown.source.is_empty() {
+        date = date.max(page.markdown.modified);
+    }
+    if let Some(html) = &page.html {
+        date = date.max(html.modified);
+    }
+    if let Some(json) = &page.json {
+        date = date.max(json.modified);
+    }
+    date
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_date;
+
+    #[test]
+    fn dates_with_and_without_times() {
+        let day = parse_date("2024-03-01").unwrap();
+        assert_eq!(day.to_rfc3339(), "2024-03-01T00:00:00+00:00");
+        assert_eq!(
+            parse_date("2024-03-01 12:30").unwrap().to_rfc3339(),
+            "2024-03-01T12:30:00+00:00"
+        );
+        assert_eq!(
+            parse_date("2024-03-01T12:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-03-01T10:00:00+00:00"
+        );
+        assert!(parse_date("March 1st").is_none());
+    }
+}
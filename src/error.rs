@@ -0,0 +1,125 @@
+//! Crate wide error type
+//!
+//! Every fallible public function in n4 returns `N4Result<T>` so a consumer such as the web server can map a
+//! failure to a response (404 for missing content, 500 for everything else) instead of the process going down.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub type N4Result<T> = Result<T, N4Error>;
+
+#[derive(Debug)]
+pub enum N4Error {
+    /// A filesystem operation failed on the given path
+    Io { path: PathBuf, source: io::Error },
+    /// There is no system config dir or no n4 config file in it
+    ConfigMissing(PathBuf),
+    /// The config file exists but isn't valid JSON for a `SiteConfig`
+    ConfigParse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// A config value is present but unusable, like a base_dir without a trailing delimiter
+    InvalidConfig(String),
+    /// A file that would be created already exists
+    AlreadyExists(PathBuf),
+    /// A .content_meta or .menu_meta file couldn't be deserialized
+    MetaParse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// Front matter at the top of a markdown file isn't valid YAML or TOML
+    FrontMatter { path: PathBuf, reason: String },
+    /// A struct couldn't be serialized for writing to disk
+    Serialize(serde_json::Error),
+    /// A path resolved to somewhere outside of the content root
+    PathEscape(String),
+    /// The requested content doesn't exist in any of the supported formats
+    NotFound(String),
+    /// A template failed to load or render
+    Template(tera::Error),
+    /// A saved search index isn't valid JSON for a `SearchIndex`
+    SearchIndexParse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl N4Error {
+    /// Wraps an io::Error with the path it happened on
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        N4Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// True when the error means the requested thing simply isn't there, the 404 case for a web server
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            N4Error::NotFound(_) | N4Error::PathEscape(_) => true,
+            N4Error::Io { source, .. } => source.kind() == io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for N4Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            N4Error::Io { path, source } => {
+                write!(f, "IO error on {}: {}", path.to_string_lossy(), source)
+            }
+            N4Error::ConfigMissing(path) => {
+                write!(f, "Config file not found: {}", path.to_string_lossy())
+            }
+            N4Error::ConfigParse { path, source } => write!(
+                f,
+                "Config couldn't be deserialized: {} -> {}",
+                path.to_string_lossy(),
+                source
+            ),
+            N4Error::InvalidConfig(why) => write!(f, "Invalid config: {}", why),
+            N4Error::AlreadyExists(path) => {
+                write!(f, "File already exists: {}", path.to_string_lossy())
+            }
+            N4Error::MetaParse { path, source } => write!(
+                f,
+                "Bad metadata JSON: {} -> {}",
+                path.to_string_lossy(),
+                source
+            ),
+            N4Error::FrontMatter { path, reason } => write!(
+                f,
+                "Bad front matter: {} -> {}",
+                path.to_string_lossy(),
+                reason
+            ),
+            N4Error::Serialize(source) => write!(f, "Serialize to json fail: {}", source),
+            N4Error::PathEscape(path) => write!(f, "Path escapes the content root: {}", path),
+            N4Error::NotFound(path) => write!(f, "Content not found: {}", path),
+            N4Error::Template(source) => write!(f, "Template error: {}", source),
+            N4Error::SearchIndexParse { path, source } => write!(
+                f,
+                "Bad search index JSON: {} -> {}",
+                path.to_string_lossy(),
+                source
+            ),
+        }
+    }
+}
+
+impl Error for N4Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            N4Error::Io { source, .. } => Some(source),
+            N4Error::ConfigParse { source, .. } => Some(source),
+            N4Error::MetaParse { source, .. } => Some(source),
+            N4Error::Serialize(source) => Some(source),
+            N4Error::Template(source) => Some(source),
+            N4Error::SearchIndexParse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
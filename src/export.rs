@@ -0,0 +1,195 @@
+//! Static site export
+//!
+//! Renders every content item to `<out_dir>/<location>/index.html` and writes the sitemap files, robots.txt and the
+//! compact search index next to them.  The location is the page's ContentMeta.path when it's set and its web path
+//! otherwise, the same one the sitemap lists.  A page named `index` is the page for its directory, so `/docs/index`
+//! lands in `<out_dir>/docs/index.html`.  One bad page doesn't stop the export, it's recorded in the report and the rest carry
+//! on.  Hidden `.` files and directories are left out like they are from the sitemap, and drafts, scheduled and
+//! expired pages are skipped unless SiteConfig.preview is on.  Two pages that land on the same file, `/docs` and
+//! `/docs/index`, are a failure for the second one.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{N4Error, N4Result};
+use crate::sitemap::render_sitemaps;
+use crate::web_path::WebPath;
+use crate::{assemble_page, published_path, ContentMeta, PageContent, Site};
+
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    /// Output files for the pages that made it, in web path order
+    pub pages_written: Vec<PathBuf>,
+    /// sitemap.xml, robots.txt, the search index and anything else that isn't a page
+    pub files_written: Vec<PathBuf>,
+    pub failures: Vec<ExportFailure>,
+}
+
+#[derive(Debug)]
+pub struct ExportFailure {
+    pub web_path: String,
+    pub error: N4Error,
+}
+
+impl ExportReport {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for ExportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Exported {} pages and {} other files, {} failures",
+            self.pages_written.len(),
+            self.files_written.len(),
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            writeln!(f, "  {}: {}", failure.web_path, failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Site {
+    /// Exports the whole site to out_dir, each page rendered through its template
+    pub fn export_site(&self, out_dir: &Path) -> N4Result<ExportReport> {
+        let templates = self.templates()?;
+        let menus = self.tree_to_menus(self.generate_content_state()?)?;
+        self.export_site_with(out_dir, &|page: &PageContent| {
+            let sections = self.ancestor_section_metas(&page.web_path);
+            templates.render_page(page, &menus, &sections, self)
+        })
+    }
+
+    /// Exports the whole site to out_dir rendering each page with the given function.  Only problems with the
+    /// output directory or the content tree itself are an Err, page level problems end up in the report.
+    pub fn export_site_with(
+        &self,
+        out_dir: &Path,
+        render: &dyn Fn(&PageContent) -> N4Result<String>,
+    ) -> N4Result<ExportReport> {
+        fs::create_dir_all(out_dir).map_err(|why| N4Error::io(out_dir, why))?;
+        let dir_tree = self.generate_content_state()?;
+        let mut report = ExportReport::default();
+        let pages = self.wiki_pages();
+        // `/docs` and `/docs/index` both want docs/index.html, the first one keeps it
+        let mut outputs: HashMap<PathBuf, WebPath> = HashMap::new();
+
+        for web_path in self.content_items(&dir_tree)? {
+            if web_path.is_hidden() {
+                continue;
+            }
+            // Drafts and pages outside their publishing window don't go out unless previewing
+            let page = match assemble_page(&pages, web_path.clone(), &mut Vec::new()) {
+                Ok(page) if !self.config().is_listed(&page.meta) => continue,
+                Ok(page) => page,
+                Err(error) => {
+                    report.failures.push(ExportFailure {
+                        web_path: web_path.to_string(),
+                        error,
+                    });
+                    continue;
+                }
+            };
+            let output = page_output_path(out_dir, &web_path, &page.meta);
+            let result = match outputs.get(&output) {
+                Some(first) => Err(N4Error::InvalidConfig(format!(
+                    "{} and {} both export to {}",
+                    first,
+                    web_path,
+                    output.to_string_lossy()
+                ))),
+                None => render(&page).and_then(|html| write_output(&output, html)),
+            };
+            if result.is_ok() {
+                outputs.insert(output, web_path.clone());
+            }
+            match result {
+                Ok(written) => report.pages_written.push(written),
+                Err(error) => report.failures.push(ExportFailure {
+                    web_path: web_path.to_string(),
+                    error,
+                }),
+            }
+        }
+
+        let sitemap_files = render_sitemaps(
+            &self.tree_to_sitemap(&dir_tree)?,
+            &self.config().prod_host,
+            &self.sitemap_options(),
+        )?;
+        for sitemap_file in sitemap_files {
+            report.files_written.push(write_output(
+                &out_dir.join(&sitemap_file.name),
+                &sitemap_file.contents,
+            )?);
+        }
+        report.files_written.push(write_output(
+            &out_dir.join("robots.txt"),
+            self.generate_robot_food()?,
+        )?);
+        if self.config().search.export {
+            report.files_written.push(write_output(
+                &out_dir.join(&self.config().search.export_file),
+                self.search_index()?.to_compact_json()?,
+            )?);
+        }
+        Ok(report)
+    }
+}
+
+impl Site {
+    /// The pages export_site writes, by the directory each one lands in, see page_output_dir.  Hidden and unlisted
+    /// pages and ones whose meta can't be read are left out, and of two pages landing in the same place the first
+    /// keeps it, the same as the export.
+    pub(crate) fn exported_pages(&self, web_paths: &[WebPath]) -> HashMap<WebPath, WebPath> {
+        let mut pages: HashMap<WebPath, WebPath> = HashMap::new();
+        for web_path in web_paths {
+            if web_path.is_hidden() {
+                continue;
+            }
+            let meta = self
+                .checked_path(web_path, None)
+                .and_then(|x| self.peek_content_meta(&x));
+            match meta {
+                Ok(meta) if self.config().is_listed(&meta) => {
+                    pages
+                        .entry(page_output_dir(web_path, &meta))
+                        .or_insert_with(|| web_path.clone());
+                }
+                Ok(_) => {}
+                Err(why) => println!("Export meta failure. {}", why),
+            }
+        }
+        pages
+    }
+}
+
+/// Where a page ends up in the export, at its published location with `index` pages standing in for their directory
+pub fn page_output_path(out_dir: &Path, web_path: &WebPath, meta: &ContentMeta) -> PathBuf {
+    page_output_dir(web_path, meta)
+        .to_local(out_dir)
+        .join("index.html")
+}
+
+/// The directory a page is exported into at its published location, and so what its relative links resolve against
+pub(crate) fn page_output_dir(web_path: &WebPath, meta: &ContentMeta) -> WebPath {
+    let location = published_path(web_path, meta);
+    if location.file_name() == Some("index") {
+        location.parent()
+    } else {
+        location
+    }
+}
+
+fn write_output<C: AsRef<[u8]>>(path: &Path, contents: C) -> N4Result<PathBuf> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|why| N4Error::io(parent, why))?;
+    }
+    fs::write(path, contents).map_err(|why| N4Error::io(path, why))?;
+    Ok(path.to_path_buf())
+}
@@ -0,0 +1,257 @@
+//! Syndication feeds
+//!
+//! `Site::generate_feed` writes an Atom 1.0 or RSS 2.0 feed for the content under a section, every page below it
+//! and not only the ones directly in it, or for the whole site with `/`.  Entries take their title, description
+//! and author from `ContentMeta`, their published date from `ContentMeta.published` or else the content's created
+//! time and their updated date from `ContentMeta.updated` or else its modified time, newest published first.  The
+//! content is the rendered markdown followed by any HTML body.  `index` pages are the listings themselves and aren't
+//! entries, hidden and noindex pages are left out like they are from the sitemap, as are drafts, scheduled and
+//! expired pages.  A page that can't be read is logged and left out rather than failing the feed.
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::N4Result;
+use crate::sitemap::{url_encode, xml_escape};
+use crate::web_path::WebPath;
+use crate::{content_location, unix_time_to_iso, PageContent, PageSource, Site};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Most entries in a feed
+    pub items: usize,
+    /// Feed title, empty uses prod_host
+    pub title: String,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        FeedConfig {
+            items: 20,
+            title: String::new(),
+        }
+    }
+}
+
+// One page ready to be written as either kind of entry
+struct FeedItem {
+    url: String,
+    title: String,
+    description: String,
+    author: String,
+    published: DateTime<Utc>,
+    updated: DateTime<Utc>,
+    content: String,
+}
+
+impl Site {
+    /// An Atom or RSS feed for the pages under a section, `/` for the whole site
+    pub fn generate_feed(&self, section: &str, format: FeedFormat) -> N4Result<String> {
+        let section = WebPath::parse(section)?;
+        let mut items: Vec<FeedItem> = Vec::new();
+        let pages = self.wiki_pages();
+        for web_path in self.content_items(&self.generate_content_state()?)? {
+            let is_entry = web_path.starts_with(&section)
+                && web_path.file_name() != Some("index")
+                && !web_path.is_hidden();
+            if !is_entry {
+                continue;
+            }
+            match pages.page_parts(&web_path) {
+                Ok(page) if !page.meta.noindex && self.config().is_listed(&page.meta) => {
+                    items.push(self.feed_item(&web_path, page))
+                }
+                Ok(_) => {}
+                Err(why) => println!("Feed failure. {}", why),
+            }
+        }
+        items.sort_by(|a, b| {
+            b.published
+                .cmp(&a.published)
+                .then_with(|| a.url.cmp(&b.url))
+        });
+        items.truncate(self.config().feed.items);
+
+        let title = if self.config().feed.title.is_empty() {
+            self.config().prod_host.clone()
+        } else {
+            self.config().feed.title.clone()
+        };
+        let title = match section.file_name() {
+            Some(val) => format!("{}: {}", title, val),
+            None => title,
+        };
+        // The root has no .menu_meta, its default description isn't worth publishing
+        let description = if section.is_root() {
+            String::new()
+        } else {
+            self.section_meta_of(&section)?.description
+        };
+        // Where the section's index page is published
+        let link = if section.is_root() {
+            self.config().absolute_url("/")
+        } else {
+            self.config()
+                .absolute_url(&format!("{}/", url_encode(&section.to_string())))
+        };
+        Ok(match format {
+            FeedFormat::Atom => atom(&title, &description, &link, &items),
+            FeedFormat::Rss => rss(&title, &description, &link, &items),
+        })
+    }
+
+    fn feed_item(&self, web_path: &WebPath, mut page: PageContent) -> FeedItem {
+        let mut created = unix_time_to_iso(0.0);
+        let mut updated = unix_time_to_iso(0.0);
+        let mut content = String::new();
+        if !page.markdown.source.is_empty() {
+            created = page.markdown.created;
+            updated = page.markdown.modified;
+            content.push_str(page.markdown.render());
+        }
+        if let Some(html) = &page.html {
+            if content.is_empty() {
+                created = html.created;
+            }
+            updated = updated.max(html.modified);
+            content.push_str(&html.body);
+        }
+        let published = page.meta.published.unwrap_or(created);
+        if let Some(val) = page.meta.updated {
+            updated = val;
+        }
+        FeedItem {
+            url: self
+                .config()
+                .absolute_url(&url_encode(&content_location(web_path, &page.meta))),
+            title: page.meta.title,
+            description: page.meta.description,
+            author: page.meta.author,
+            published,
+            updated: updated.max(published),
+            content,
+        }
+    }
+}
+
+fn atom(title: &str, subtitle: &str, link: &str, items: &[FeedItem]) -> String {
+    let updated = items
+        .iter()
+        .map(|x| x.updated)
+        .max()
+        .unwrap_or_else(|| unix_time_to_iso(0.0));
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{}</id>\n", xml_escape(link)));
+    xml.push_str(&format!("  <title>{}</title>\n", xml_escape(title)));
+    if !subtitle.is_empty() {
+        xml.push_str(&format!(
+            "  <subtitle>{}</subtitle>\n",
+            xml_escape(subtitle)
+        ));
+    }
+    xml.push_str(&format!("  <link href=\"{}\"/>\n", xml_escape(link)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated.to_rfc3339()));
+    for item in items {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", xml_escape(&item.url)));
+        xml.push_str(&format!("    <title>{}</title>\n", xml_escape(&item.title)));
+        xml.push_str(&format!("    <link href=\"{}\"/>\n", xml_escape(&item.url)));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            item.published.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            item.updated.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <author><name>{}</name></author>\n",
+            xml_escape(&item.author)
+        ));
+        if !item.description.is_empty() {
+            xml.push_str(&format!(
+                "    <summary>{}</summary>\n",
+                xml_escape(&item.description)
+            ));
+        }
+        xml.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            xml_escape(&item.content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss(title: &str, description: &str, link: &str, items: &[FeedItem]) -> String {
+    let updated = items
+        .iter()
+        .map(|x| x.updated)
+        .max()
+        .unwrap_or_else(|| unix_time_to_iso(0.0));
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(
+        "<rss version=\"2.0\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" ",
+    );
+    xml.push_str("xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", xml_escape(title)));
+    xml.push_str(&format!("    <link>{}</link>\n", xml_escape(link)));
+    // Required in RSS, fall back to the title
+    let description = if description.is_empty() {
+        title
+    } else {
+        description
+    };
+    xml.push_str(&format!(
+        "    <description>{}</description>\n",
+        xml_escape(description)
+    ));
+    xml.push_str(&format!(
+        "    <lastBuildDate>{}</lastBuildDate>\n",
+        updated.to_rfc2822()
+    ));
+    for item in items {
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!(
+            "      <title>{}</title>\n",
+            xml_escape(&item.title)
+        ));
+        xml.push_str(&format!("      <link>{}</link>\n", xml_escape(&item.url)));
+        xml.push_str(&format!(
+            "      <guid isPermaLink=\"true\">{}</guid>\n",
+            xml_escape(&item.url)
+        ));
+        xml.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            item.published.to_rfc2822()
+        ));
+        // RSS author has to be an email address, the name goes in dc:creator
+        xml.push_str(&format!(
+            "      <dc:creator>{}</dc:creator>\n",
+            xml_escape(&item.author)
+        ));
+        if !item.description.is_empty() {
+            xml.push_str(&format!(
+                "      <description>{}</description>\n",
+                xml_escape(&item.description)
+            ));
+        }
+        xml.push_str(&format!(
+            "      <content:encoded>{}</content:encoded>\n",
+            xml_escape(&item.content)
+        ));
+        xml.push_str("    </item>\n");
+    }
+    xml.push_str("  </channel>\n</rss>\n");
+    xml
+}
@@ -1,13 +1,15 @@
-use serde;
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::error::{N4Error, N4Result};
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct DirTree {
     pub absolute_path: String,
     pub relative_path: String,
@@ -16,19 +18,7 @@ pub struct DirTree {
     pub directories: HashMap<String, DirTree>,
 }
 
-impl Default for DirTree {
-    fn default() -> Self {
-        DirTree {
-            absolute_path: String::new(),
-            relative_path: String::new(),
-            dir_meta: FileMeta::default(),
-            files: HashMap::new(),
-            directories: HashMap::new(),
-        }
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FileMeta {
     pub created: f64,
     pub modified: f64,
@@ -48,12 +38,12 @@ impl Default for FileMeta {
 pub fn files_in_tree(dir_tree: DirTree) -> Vec<String> {
     let mut files: Vec<String> = Vec::new();
 
-    if dir_tree.files.len() > 0 {
+    if !dir_tree.files.is_empty() {
         for filename in dir_tree.files.keys() {
             files.push(format!("{}/{}", dir_tree.relative_path, filename))
         }
     }
-    if dir_tree.directories.len() > 0 {
+    if !dir_tree.directories.is_empty() {
         for _dir_tree in dir_tree.directories {
             files.append(&mut files_in_tree(_dir_tree.1));
         }
@@ -62,32 +52,60 @@ pub fn files_in_tree(dir_tree: DirTree) -> Vec<String> {
     files
 }
 
-pub fn get_file_metadata(_this_file_path: PathBuf) -> FileMeta {
-    let this_file_metadata = match fs::metadata(_this_file_path) {
-        Ok(_this_meta) => _this_meta,
-        Err(_err) => panic!("{}", _err), // TODO Remove panic
-    };
+pub fn get_file_metadata(this_file_path: PathBuf) -> N4Result<FileMeta> {
+    let this_file_metadata =
+        fs::metadata(&this_file_path).map_err(|err| N4Error::io(&this_file_path, err))?;
+    let modified = this_file_metadata
+        .modified()
+        .map_err(|err| N4Error::io(&this_file_path, err))?;
+    // Not every filesystem records a birth time, fall back to modified rather than failing the whole tree
+    let created = this_file_metadata.created().unwrap_or(modified);
 
-    FileMeta {
-        created: match this_file_metadata.created() {
-            Ok(_created) => match _created.duration_since(UNIX_EPOCH) {
-                Ok(_created_after) => _created_after.as_secs_f64(),
-                Err(_err) => panic!("{}", _err), // TODO Remove panic
-            },
-            Err(_err) => panic!("{}", _err),
-        },
-        modified: match this_file_metadata.modified() {
-            Ok(_modified) => match _modified.duration_since(UNIX_EPOCH) {
-                Ok(_modified_after) => _modified_after.as_secs_f64(),
-                Err(_err) => panic!("{}", _err), // TODO Remove panic
-            },
-            Err(_err) => panic!("{}", _err), // TODO Remove panic
-        },
+    Ok(FileMeta {
+        created: system_time_to_unix(created),
+        modified: system_time_to_unix(modified),
         size: this_file_metadata.len(),
+    })
+}
+
+// Times before the epoch are clamped to it, they only come from broken clocks or archives
+fn system_time_to_unix(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch.as_secs_f64(),
+        Err(_) => 0.0,
     }
 }
 
-pub fn dir_to_tree(path: &str, relative: &str) -> DirTree {
+/// Walks a directory into a DirTree.  Symlinks are followed only while they stay inside `path`, and a symlinked
+/// directory that's already been walked, like one pointing back up the tree, is left out.  Only `path` itself
+/// failing to read is an Err, a file or subdirectory that can't be read is logged and left out.
+pub fn dir_to_tree(path: &str, relative: &str) -> N4Result<DirTree> {
+    dir_subtree(path, relative, Path::new(path))
+}
+
+/// dir_to_tree for a directory under `root`, symlinks are held to `root` rather than the directory itself
+pub(crate) fn dir_subtree(path: &str, relative: &str, root: &Path) -> N4Result<DirTree> {
+    let canonical_root = fs::canonicalize(root).map_err(|err| N4Error::io(root, err))?;
+    let canonical = fs::canonicalize(path).map_err(|err| N4Error::io(Path::new(path), err))?;
+    if !canonical.starts_with(&canonical_root) {
+        return Err(N4Error::PathEscape(path.to_string()));
+    }
+    // The directories above count as walked, a link back up to one of them would walk this one again
+    let mut visited: HashSet<PathBuf> = canonical
+        .ancestors()
+        .skip(1)
+        .take_while(|x| x.starts_with(&canonical_root))
+        .map(Path::to_path_buf)
+        .collect();
+    walk_dir(path, relative, &canonical_root, &mut visited)
+}
+
+fn walk_dir(
+    path: &str,
+    relative: &str,
+    canonical_root: &Path,
+    visited: &mut HashSet<PathBuf>,
+) -> N4Result<DirTree> {
     let mut dir_tree: DirTree = DirTree::default();
     let mut current_path = Path::new(path).components();
     // INFO both paths may not be necessary here, may deprecate absolute_path later
@@ -97,26 +115,29 @@ pub fn dir_to_tree(path: &str, relative: &str) -> DirTree {
         relative,
         current_path
             .nth_back(0)
-            .unwrap()
-            .as_os_str()
-            .to_string_lossy()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .unwrap_or_default()
     );
-    if _temp_string.starts_with("/") {
-        dir_tree.relative_path = _temp_string.strip_prefix("/").unwrap().to_string();
-    } else {
-        dir_tree.relative_path = _temp_string;
-    }
-    dir_tree.dir_meta = get_file_metadata(PathBuf::from(path));
-
-    let paths = match fs::read_dir(path) {
-        Ok(_paths) => _paths,
-        Err(_err) => panic!("{}", _err),
+    dir_tree.relative_path = match _temp_string.strip_prefix('/') {
+        Some(stripped) => stripped.to_string(),
+        None => _temp_string,
     };
+    dir_tree.dir_meta = get_file_metadata(PathBuf::from(path))?;
+    visited.insert(fs::canonicalize(path).map_err(|err| N4Error::io(Path::new(path), err))?);
 
+    let paths = fs::read_dir(path).map_err(|err| N4Error::io(Path::new(path), err))?;
+
+    // An entry that can't be read is logged and left out, one bad file shouldn't hide the rest of the tree
     for path in paths {
         let this_path = match path {
-            Ok(_potential_path) => _potential_path.path(),
-            Err(_err) => panic!("{}", _err), // TODO Remove panic
+            Ok(val) => val.path(),
+            Err(why) => {
+                println!(
+                    "Tree failure. {}",
+                    N4Error::io(Path::new(&dir_tree.absolute_path), why)
+                );
+                continue;
+            }
         };
 
         let this_file_stem: String = String::from(match this_path.file_stem() {
@@ -124,18 +145,104 @@ pub fn dir_to_tree(path: &str, relative: &str) -> DirTree {
             None => Cow::Borrowed("ERROR_File_stem_not_parsed"),
         });
 
-        if this_path.is_dir() {
-            dir_tree.directories.insert(
-                this_file_stem,
-                dir_to_tree(&this_path.to_string_lossy(), &dir_tree.relative_path),
-            );
+        // symlink_metadata so a link is seen as a link, not as whatever it points at
+        let link_meta = match fs::symlink_metadata(&this_path) {
+            Ok(val) => val,
+            Err(why) => {
+                println!("Tree failure. {}", N4Error::io(&this_path, why));
+                continue;
+            }
+        };
+        let is_dir = if link_meta.file_type().is_symlink() {
+            let target = match fs::canonicalize(&this_path) {
+                Ok(val) => val,
+                Err(why) => {
+                    println!("Tree failure. {}", N4Error::io(&this_path, why));
+                    continue;
+                }
+            };
+            if !target.starts_with(canonical_root) {
+                println!(
+                    "Tree failure. {} links out of the content root",
+                    this_path.display()
+                );
+                continue;
+            }
+            if target.is_dir() && visited.contains(&target) {
+                println!(
+                    "Tree failure. {} links to a directory already walked",
+                    this_path.display()
+                );
+                continue;
+            }
+            target.is_dir()
         } else {
-            dir_tree
-                .files
-                .insert(this_file_stem, get_file_metadata(this_path));
+            link_meta.is_dir()
+        };
+
+        let walked = if is_dir {
+            walk_dir(
+                &this_path.to_string_lossy(),
+                &dir_tree.relative_path,
+                canonical_root,
+                visited,
+            )
+            .map(|subtree| {
+                dir_tree.directories.insert(this_file_stem, subtree);
+            })
+        } else {
+            get_file_metadata(this_path).map(|file_meta| {
+                dir_tree.files.insert(this_file_stem, file_meta);
+            })
+        };
+        if let Err(why) = walked {
+            println!("Tree failure. {}", why);
+        }
+    }
+    Ok(dir_tree)
+}
+
+/// A fingerprint of everything under a directory from each entry's path, size and modified time, for a cache built
+/// from the files to tell it's stale without reading them.  Symlinks count by what they point at and are held to
+/// the directory like dir_to_tree holds them, entries that can't be read don't count.
+pub(crate) fn tree_stamp(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    if let Ok(canonical_root) = fs::canonicalize(path) {
+        stamp_dir(path, &canonical_root, &mut HashSet::new(), &mut hasher);
+    }
+    hasher.finish()
+}
+
+fn stamp_dir(
+    path: &Path,
+    canonical_root: &Path,
+    visited: &mut HashSet<PathBuf>,
+    hasher: &mut DefaultHasher,
+) {
+    match fs::canonicalize(path) {
+        Ok(canonical) if canonical.starts_with(canonical_root) => {
+            if !visited.insert(canonical) {
+                return;
+            }
+        }
+        _ => return,
+    }
+    let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(val) => val.filter_map(|x| x.ok()).map(|x| x.path()).collect(),
+        Err(_) => return,
+    };
+    // read_dir order isn't stable, the stamp has to be
+    entries.sort();
+    for entry in entries {
+        if let Ok(meta) = fs::metadata(&entry) {
+            entry.hash(hasher);
+            meta.len().hash(hasher);
+            meta.modified().ok().hash(hasher);
+            if meta.is_dir() {
+                stamp_dir(&entry, canonical_root, visited, hasher);
+            }
         }
     }
-    dir_tree
 }
 
 #[cfg(test)]
@@ -144,4 +251,10 @@ mod tests {
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn missing_dir_is_an_error_not_a_panic() {
+        let result = super::dir_to_tree("/this/path/should/not/exist/n4", "");
+        assert!(result.unwrap_err().is_not_found());
+    }
 }
@@ -0,0 +1,129 @@
+//! Front matter at the top of markdown files
+//!
+//! A markdown file can carry its ContentMeta fields in a YAML block fenced by `---` lines or a TOML block fenced by
+//! `+++` lines, like:
+//!
+//! ```text
+//! ---
+//! title: Hello
+//! weight: 10
+//! ---
+//! # The body starts here
+//! ```
+//!
+//! The block is stripped before the markdown is rendered.  It only counts as front matter when it's a map, so a page
+//! that opens with a `---` thematic break and has another one further down is still all markdown.  When a page has
+//! both front matter and a `.content_meta` sidecar the sidecar is read first and the front matter fields are laid
+//! over it, so the file the writer is editing wins.
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::error::{N4Error, N4Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontMatterFormat {
+    Yaml,
+    Toml,
+}
+
+impl FrontMatterFormat {
+    fn fence(&self) -> &'static str {
+        match self {
+            FrontMatterFormat::Yaml => "---",
+            FrontMatterFormat::Toml => "+++",
+        }
+    }
+}
+
+/// Splits a markdown source into its front matter format, the raw front matter and the body.  None when the source
+/// doesn't open with a fence or the block is never closed, in which case it's all body.
+pub fn split_front_matter(source: &str) -> Option<(FrontMatterFormat, &str, &str)> {
+    let format = [FrontMatterFormat::Yaml, FrontMatterFormat::Toml]
+        .iter()
+        .cloned()
+        .find(|x| first_line(source) == x.fence())?;
+    let raw_start = source.find('\n')? + 1;
+    let mut line_start = raw_start;
+    while line_start < source.len() {
+        let line_end = source[line_start..]
+            .find('\n')
+            .map(|x| line_start + x + 1)
+            .unwrap_or_else(|| source.len());
+        if source[line_start..line_end].trim_end() == format.fence() {
+            return Some((format, &source[raw_start..line_start], &source[line_end..]));
+        }
+        line_start = line_end;
+    }
+    None
+}
+
+/// The markdown with any front matter removed
+pub fn strip_front_matter(source: &str) -> &str {
+    match split_front_matter(source) {
+        Some((format, raw, body)) if matches!(parse_raw(format, raw), Ok(Value::Object(_))) => body,
+        _ => source,
+    }
+}
+
+/// Front matter of a markdown source as a JSON object ready to be laid over a ContentMeta, path is only for errors
+pub fn front_matter_value(path: &Path, source: &str) -> N4Result<Option<Value>> {
+    Ok(parse_front_matter(path, source)?.0)
+}
+
+/// Splits a markdown source into its front matter, as a JSON object, and the body.  A block that parses to anything
+/// but a map isn't front matter, `---` is a thematic break in markdown too, so the whole source is body then.
+pub fn parse_front_matter<'a>(path: &Path, source: &'a str) -> N4Result<(Option<Value>, &'a str)> {
+    let (format, raw, body) = match split_front_matter(source) {
+        Some(val) => val,
+        None => return Ok((None, source)),
+    };
+    match parse_raw(format, raw) {
+        Ok(value @ Value::Object(_)) => Ok((Some(value), body)),
+        Ok(_) => Ok((None, source)),
+        Err(reason) => Err(N4Error::FrontMatter {
+            path: path.to_path_buf(),
+            reason,
+        }),
+    }
+}
+
+fn parse_raw(format: FrontMatterFormat, raw: &str) -> Result<Value, String> {
+    match format {
+        FrontMatterFormat::Yaml if raw.trim().is_empty() => Ok(Value::Object(Default::default())),
+        FrontMatterFormat::Yaml => serde_yaml::from_str(raw).map_err(|why| why.to_string()),
+        FrontMatterFormat::Toml => toml::from_str(raw).map_err(|why| why.to_string()),
+    }
+}
+
+fn first_line(source: &str) -> &str {
+    source.lines().next().unwrap_or("").trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_front_matter, strip_front_matter, FrontMatterFormat};
+
+    #[test]
+    fn splits_yaml_and_toml() {
+        let (format, raw, body) = split_front_matter("---\ntitle: Hi\n---\n# Body\n").unwrap();
+        assert_eq!(format, FrontMatterFormat::Yaml);
+        assert_eq!(raw, "title: Hi\n");
+        assert_eq!(body, "# Body\n");
+
+        let (format, raw, body) =
+            split_front_matter("+++\r\ntitle = \"Hi\"\r\n+++\r\nBody").unwrap();
+        assert_eq!(format, FrontMatterFormat::Toml);
+        assert_eq!(raw, "title = \"Hi\"\r\n");
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn unclosed_or_missing_fences_are_body() {
+        assert!(split_front_matter("# Just markdown\n---\n").is_none());
+        assert_eq!(
+            strip_front_matter("---\nnever closed\n"),
+            "---\nnever closed\n"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Syntax highlighting for fenced code blocks
+//!
+//! With `MarkdownOptions.highlight` on, fenced code with a language goes through syntect as comrak's syntax
+//! highlighter plugin.  The output only carries classes, like `<span class="source rust">`, so the colors come from a
+//! stylesheet: `highlight_css` turns any of syntect's bundled themes into one and `Site::highlight_css` does it for
+//! `SiteConfig.highlight_theme`.
+use std::sync::OnceLock;
+
+use comrak::plugins::syntect::{SyntectAdapter, SyntectAdapterBuilder};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle};
+
+use crate::error::{N4Error, N4Result};
+use crate::Site;
+
+pub const DEFAULT_HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+/// The class based adapter, loading syntect's syntaxes is slow enough to only do it once per process
+pub(crate) fn adapter() -> &'static SyntectAdapter {
+    static ADAPTER: OnceLock<SyntectAdapter> = OnceLock::new();
+    ADAPTER.get_or_init(|| SyntectAdapterBuilder::new().css().build())
+}
+
+/// Names of the themes highlight_css knows
+pub fn highlight_themes() -> Vec<String> {
+    let mut themes: Vec<String> = ThemeSet::load_defaults().themes.into_keys().collect();
+    themes.sort();
+    themes
+}
+
+/// The stylesheet for highlighted code in one of syntect's bundled themes
+pub fn highlight_css(theme: &str) -> N4Result<String> {
+    let themes = ThemeSet::load_defaults();
+    let theme = themes.themes.get(theme).ok_or_else(|| {
+        N4Error::InvalidConfig(format!(
+            "Unknown highlight theme {}, available are: {}",
+            theme,
+            highlight_themes().join(", ")
+        ))
+    })?;
+    css_for_theme_with_class_style(theme, ClassStyle::Spaced)
+        .map_err(|why| N4Error::InvalidConfig(format!("Highlight theme CSS failed: {}", why)))
+}
+
+impl Site {
+    /// The stylesheet for SiteConfig.highlight_theme
+    pub fn highlight_css(&self) -> N4Result<String> {
+        highlight_css(&self.config().highlight_theme)
+    }
+}
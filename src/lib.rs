@@ -9,47 +9,182 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::UNIX_EPOCH;
 
-use chrono;
 use chrono::prelude::*;
-use comrak::{markdown_to_html, ComrakOptions};
-use dirs;
 //use markdown;
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
-use v_htmlescape::escape;
-
-// Currently a development dependency, probably going to move to a module
+use serde_json::Value;
+
+pub mod blog;
+pub mod content_index;
+pub mod dates;
+pub mod error;
+pub mod export;
+pub mod feed;
+pub mod file_tree;
+pub mod front_matter;
+pub mod highlight;
+pub mod links;
+pub mod markdown;
+pub mod robots;
+pub mod sanitize;
+pub mod search;
+pub mod sitemap;
+pub mod taxonomy;
+pub mod templates;
+pub mod toc;
+pub mod web_path;
+pub mod wiki;
+
+pub use blog::{ArchiveMonth, ArchiveYear, BlogPage, ListingMode, PostLinks, PostSummary};
+pub use content_index::{ContentEvent, ContentIndex};
+pub use dates::{page_date, parse_date};
+pub use error::{N4Error, N4Result};
+pub use export::{ExportFailure, ExportReport};
+pub use feed::{FeedConfig, FeedFormat};
 use file_tree::*;
+use front_matter::{front_matter_value, parse_front_matter, strip_front_matter};
+pub use highlight::{highlight_css, highlight_themes};
+pub use links::{BrokenLink, LinkProblem, LinkReport};
+pub use markdown::{
+    render_markdown, render_markdown_with_wiki, MarkdownOptions, MarkdownOverrides,
+};
+pub use robots::{RobotsConfig, RobotsGroup};
+pub use sanitize::SanitizeConfig;
+pub use search::{SearchConfig, SearchHit, SearchIndex};
+pub use sitemap::{render_sitemap_xml, render_sitemaps, ChangeFreq, SitemapFile, SitemapOptions};
+pub use taxonomy::{CloudEntry, Taxonomy, TaxonomyKind, TaxonomyTerm, TermPage, TermSort};
+pub use templates::{PageIncludes, Templates};
+pub use toc::{markdown_toc, slugify, TocEntry};
+pub use web_path::WebPath;
+use wiki::SharedWiki;
+pub use wiki::WikiIndex;
 
 /// Struct to hold the site configuration
 ///
 /// prod_host
 ///     sitemap-data: Production protocol and FQDN such as https://slashdot.org/
 /// xml_priority: String
-///     sitemap-data: Just 0.64 normally for sitemap.xml
+///     sitemap-data: Just 0.64 normally for sitemap.xml, pages and sections can override it with sitemap_priority
 /// base_dir
 ///     content-data: Relative root directory name of the content
 /// local_content_dir
 ///     content-data: Absolute path to content directory, concatenated with base dir on end
-#[derive(Serialize, Deserialize, Debug)]
+/// content_list_max_depth: usize
+///     content-data: How deep content_list pages can nest before the list is cut off, optional, defaults to 8
+/// template_dir: String
+///     render-data: Absolute path to a directory of Tera *.html templates, optional, empty uses the built in default
+/// sitemap_gzip: bool
+///     sitemap-data: Write sitemap.xml.gz and gzipped sitemap parts instead of plain XML, optional, defaults to false
+/// read_only: bool
+///     content-data: Never write to the content tree while reading it, missing meta is defaulted in memory only,
+///     optional, defaults to false
+/// markdown: MarkdownOptions
+///     render-data: comrak extensions and render options, sections and pages can override them, optional, all off
+/// highlight_theme: String
+///     render-data: syntect theme for Site::highlight_css when markdown.highlight is on, optional, InspiredGitHub
+/// sanitize: SanitizeConfig
+///     render-data: Allowed tags, attributes and URL schemes for markdown and HTML bodies, optional, on by default
+/// robots: RobotsConfig
+///     robots-data: User-agent groups and sitemap URLs for robots.txt, optional, defaults to allow everything
+/// feed: FeedConfig
+///     feed-data: Atom and RSS entry count and title, optional, defaults to 20 entries titled with prod_host
+/// search: SearchConfig
+///     search-data: Whether static exports get the compact search index and its file name, optional, defaults to
+///     search_index.json
+/// preview: bool
+///     content-data: List drafts, future dated and expired pages like any other, optional, defaults to false
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SiteConfig {
     pub prod_host: String,
     pub xml_priority: String,
     pub base_dir: String,
     pub local_content_dir: String,
+    #[serde(default = "default_content_list_max_depth")]
+    pub content_list_max_depth: usize,
+    #[serde(default)]
+    pub template_dir: String,
+    #[serde(default)]
+    pub sitemap_gzip: bool,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub markdown: MarkdownOptions,
+    #[serde(default = "default_highlight_theme")]
+    pub highlight_theme: String,
+    #[serde(default)]
+    pub sanitize: SanitizeConfig,
+    #[serde(default)]
+    pub robots: RobotsConfig,
+    #[serde(default)]
+    pub feed: FeedConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub preview: bool,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            prod_host: String::from("https://localhost:8000"),
+            xml_priority: String::from("0.64"),
+            base_dir: String::from("/"),
+            local_content_dir: String::from("/"),
+            content_list_max_depth: default_content_list_max_depth(),
+            template_dir: String::new(),
+            sitemap_gzip: false,
+            read_only: false,
+            markdown: MarkdownOptions::default(),
+            highlight_theme: default_highlight_theme(),
+            sanitize: SanitizeConfig::default(),
+            robots: RobotsConfig::default(),
+            feed: FeedConfig::default(),
+            search: SearchConfig::default(),
+            preview: false,
+        }
+    }
+}
+
+fn default_content_list_max_depth() -> usize {
+    8
+}
+
+fn default_page_size() -> usize {
+    10
+}
+
+fn default_highlight_theme() -> String {
+    String::from(highlight::DEFAULT_HIGHLIGHT_THEME)
 }
 
 impl SiteConfig {
-    pub fn local_path(self) -> String {
+    pub fn local_path(&self) -> String {
         format!("{}{}", self.local_content_dir, self.base_dir)
     }
+
+    /// Full URL for a location on the site, a trailing / on prod_host doesn't double up
+    pub fn absolute_url(&self, location: &str) -> String {
+        format!("{}{}", self.prod_host.trim_end_matches('/'), location)
+    }
+
+    /// Whether a page belongs in listings right now, always true in preview mode
+    pub fn is_listed(&self, meta: &ContentMeta) -> bool {
+        self.preview || meta.is_live_at(Utc::now())
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct PageContent {
+    // Where the page was read from, an `index` page is its directory's page
+    #[serde(default)]
+    pub web_path: WebPath,
     pub markdown: MDContent,
+    // Only filled in when the page's markdown options have toc on
+    #[serde(default)]
+    pub toc: Vec<TocEntry>,
     pub html: Option<HTMLContent>,
     pub json: Option<JSONContent>,
     pub list: Vec<PageContent>,
@@ -57,12 +192,24 @@ pub struct PageContent {
     pub section_meta: MenuItemMeta,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Markdown for a page.  `source` is the file as written minus any front matter, `rendered` is the HTML and stays
+/// None until something asks for it with MDContent::render or PageContent::render_markdown.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MDContent {
     pub created: chrono::DateTime<chrono::Utc>,
     pub modified: chrono::DateTime<chrono::Utc>,
     // pub path: String,
-    pub body: String,
+    pub source: String,
+    pub rendered: Option<String>,
+    // The page's resolved options, so a lazy render comes out the same as an eager one would have
+    #[serde(skip)]
+    pub options: MarkdownOptions,
+    // None when the section is trusted or sanitizing is off
+    #[serde(skip)]
+    pub sanitize: Option<SanitizeConfig>,
+    // Only set when the page has wiki links to resolve
+    #[serde(skip)]
+    pub wiki: Option<Arc<WikiIndex>>,
     // pub list: Vec<PageContent>, // TODO move to meta file
     // pub meta: ContentMeta,
 }
@@ -73,14 +220,18 @@ impl Default for MDContent {
             created: unix_time_to_iso(0.0),
             modified: unix_time_to_iso(0.0),
             // path: String::from("/"),
-            body: String::from("Default value"),
+            source: String::from("Default value"),
+            rendered: None,
+            options: MarkdownOptions::default(),
+            sanitize: None,
+            wiki: None,
             // list: Vec::new(), // TODO move to meta file
             // meta: ContentMeta::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct HTMLContent {
     pub created: chrono::DateTime<chrono::Utc>,
     pub modified: chrono::DateTime<chrono::Utc>,
@@ -99,7 +250,7 @@ impl Default for HTMLContent {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct JSONContent {
     pub created: chrono::DateTime<chrono::Utc>,
     pub modified: chrono::DateTime<chrono::Utc>,
@@ -130,6 +281,21 @@ pub struct ContentMeta {
     pub content_list: Vec<String>,
     pub content_type: String,
     content_class: String,
+    // Taxonomy terms, a page can be in any number of both
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    // Orders blog listings, pages without one fall back to their file times.  A future date keeps the page out of
+    // listings until then, as does draft, and expires takes it back out.
+    #[serde(default, deserialize_with = "dates::deserialize_date")]
+    pub published: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, deserialize_with = "dates::deserialize_date")]
+    pub updated: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default, deserialize_with = "dates::deserialize_date")]
+    pub expires: Option<chrono::DateTime<chrono::Utc>>,
     pub template_override: String,
     javascript_include: Vec<String>,
     javascript_inline: String,
@@ -137,6 +303,27 @@ pub struct ContentMeta {
     css_inline: String,
     created_time_default: String,
     modified_time_default: String,
+    // Sitemap overrides, unset falls back to the section then SiteConfig.xml_priority
+    #[serde(default, deserialize_with = "sitemap::deserialize_priority")]
+    pub sitemap_priority: Option<f32>,
+    #[serde(default)]
+    pub sitemap_changefreq: Option<ChangeFreq>,
+    #[serde(default)]
+    pub sitemap_exclude: Option<bool>,
+    // Keeps crawlers out through robots.txt
+    #[serde(default)]
+    pub noindex: bool,
+    #[serde(default)]
+    pub markdown: MarkdownOverrides,
+}
+
+impl ContentMeta {
+    /// Not a draft, published by `now` and not yet expired
+    pub fn is_live_at(&self, now: DateTime<Utc>) -> bool {
+        !self.draft
+            && self.published.is_none_or(|x| x <= now)
+            && self.expires.is_none_or(|x| x > now)
+    }
 }
 
 impl Default for ContentMeta {
@@ -152,6 +339,12 @@ impl Default for ContentMeta {
             content_list: Vec::new(),
             content_type: String::from("page"),
             content_class: String::from("basic-page"),
+            tags: Vec::new(),
+            categories: Vec::new(),
+            published: None,
+            updated: None,
+            draft: false,
+            expires: None,
             template_override: String::from(""),
             javascript_include: Vec::new(),
             javascript_inline: String::from(""),
@@ -159,6 +352,11 @@ impl Default for ContentMeta {
             css_inline: String::from(""),
             created_time_default: String::from("markdown"),
             modified_time_default: String::from("markdown"),
+            sitemap_priority: None,
+            sitemap_changefreq: None,
+            sitemap_exclude: None,
+            noindex: false,
+            markdown: MarkdownOverrides::default(),
         }
     }
 }
@@ -170,11 +368,13 @@ pub struct DirContent {
     relative_path: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SiteMapEntry {
     pub location: String,
     pub lastmod: DateTime<Utc>,
     pub priority: String,
+    #[serde(default)]
+    pub changefreq: Option<ChangeFreq>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -212,6 +412,20 @@ pub struct MenuItemMeta {
     section_css_include: Vec<String>, // Inherited
     css_include: Vec<String>,
     css_inline: String,
+    #[serde(default, deserialize_with = "sitemap::deserialize_priority")]
+    pub sitemap_priority: Option<f32>, // Inherited, like the other sitemap values
+    #[serde(default)]
+    pub sitemap_changefreq: Option<ChangeFreq>,
+    #[serde(default)]
+    pub sitemap_exclude: Option<bool>,
+    #[serde(default)]
+    pub markdown: MarkdownOverrides, // Inherited by the pages in the section
+    #[serde(default)]
+    pub trusted: bool, // Skips HTML sanitizing for the pages in the section
+    #[serde(default)]
+    pub listing: ListingMode, // How the section's pages are ordered, blog turns on the blog APIs
+    #[serde(default = "default_page_size")]
+    pub page_size: usize, // Posts per page in blog listings
 }
 
 impl Default for MenuItemMeta {
@@ -231,37 +445,67 @@ impl Default for MenuItemMeta {
             section_css_include: Vec::new(),
             css_include: Vec::new(),
             css_inline: String::from(""),
+            sitemap_priority: None,
+            sitemap_changefreq: None,
+            sitemap_exclude: None,
+            markdown: MarkdownOverrides::default(),
+            trusted: false,
+            listing: ListingMode::default(),
+            page_size: default_page_size(),
         }
     }
 }
 
-pub fn load_config() -> SiteConfig {
+/// The web path a page is published at, its ContentMeta.path when that's set and valid and its own web path otherwise
+pub(crate) fn published_path(web_path: &WebPath, meta: &ContentMeta) -> WebPath {
+    match WebPath::parse(&meta.path) {
+        Ok(val) if !meta.path.is_empty() => val,
+        _ => web_path.clone(),
+    }
+}
+
+/// Where a page is linked from, its ContentMeta.path or its web path when that isn't set or isn't valid.  An `index`
+/// page is its directory, `/docs/index` is linked as `/docs/` since that's where export puts it.
+pub(crate) fn content_location(web_path: &WebPath, meta: &ContentMeta) -> String {
+    let location = published_path(web_path, meta);
+    if location.file_name() != Some("index") {
+        return location.to_string();
+    }
+    match location.parent() {
+        dir if dir.is_root() => String::from("/"),
+        dir => format!("{}/", dir),
+    }
+}
+
+/// Reads the site config from the standard user config dir, `~/.config/n4/default.json` on Linux
+pub fn load_config() -> N4Result<SiteConfig> {
     let mut site_config = String::new();
     let mut config_file_path: PathBuf = match dirs::config_dir() {
-        Some(val) => PathBuf::from(val),
-        _ => panic!("No system config value found!"),
+        Some(val) => val,
+        _ => return Err(N4Error::ConfigMissing(PathBuf::from("n4/default.json"))),
     };
     config_file_path.push("n4");
     config_file_path.push("default.json");
     // File read
-    let mut _file = match fs::File::open(&config_file_path) {
-        Err(why) => panic!("Couldn't open config file: {}", why),
-        Ok(mut _file) => _file.read_to_string(&mut site_config),
+    match fs::File::open(&config_file_path) {
+        Err(_) => return Err(N4Error::ConfigMissing(config_file_path)),
+        Ok(mut _file) => _file
+            .read_to_string(&mut site_config)
+            .map_err(|why| N4Error::io(&config_file_path, why))?,
     };
     // Deserialize the JSON
-    let deserialized_site_config: SiteConfig = match serde_json::from_str(&site_config) {
-        Err(why) => panic!("Config couldn't be deserialized: {}", why),
-        Ok(value) => value,
-    };
-    deserialized_site_config
+    serde_json::from_str(&site_config).map_err(|why| N4Error::ConfigParse {
+        path: config_file_path,
+        source: why,
+    })
 }
 
 /// Creates the standard user config directory and an empty config JSON file
 /// Meant to be called from the CLI
-pub fn setup_config() {
+pub fn setup_config() -> N4Result<()> {
     let mut config_dir: PathBuf = match dirs::config_dir() {
-        Some(val) => PathBuf::from(val),
-        _ => panic!("No system config value found!"),
+        Some(val) => val,
+        _ => return Err(N4Error::ConfigMissing(PathBuf::from("n4"))),
     };
 
     config_dir.push("n4");
@@ -271,569 +515,890 @@ pub fn setup_config() {
             &config_dir.to_string_lossy()
         )
     } else {
-        match std::fs::create_dir(&config_dir) {
-            Err(why) => panic!("Directory couldn't be created: {}", why),
-            _ => println!("Created!"),
-        };
+        fs::create_dir(&config_dir).map_err(|why| N4Error::io(&config_dir, why))?;
+        println!("Created!");
     }
 
     config_dir.push("default.json");
     if config_dir.exists() {
-        panic!("Default config already exists.  Exiting.");
-    } else {
-        let default_config = SiteConfig {
-            prod_host: String::from("https://localhost:8000"),
-            xml_priority: String::from("0.64"),
-            base_dir: String::from("/"),
-            local_content_dir: String::from("/"),
-        };
-        let mut file = match fs::File::create(config_dir) {
-            Err(why) => panic!("File creation fail: {}", why),
-            Ok(value) => value,
-        };
-        let serialized_config = match serde_json::to_string_pretty(&default_config) {
-            Err(why) => panic!("Serialize to json fail: {}", why),
-            Ok(value) => value,
-        };
-        match file.write_all(&serialized_config.as_bytes()) {
-            Err(why) => panic!("Could not write to config file: {}", why),
-            Ok(_) => println!("Default config file created."),
-        };
+        return Err(N4Error::AlreadyExists(config_dir));
     }
+    let default_config = SiteConfig::default();
+    let serialized_config =
+        serde_json::to_string_pretty(&default_config).map_err(N4Error::Serialize)?;
+    let mut file = fs::File::create(&config_dir).map_err(|why| N4Error::io(&config_dir, why))?;
+    file.write_all(serialized_config.as_bytes())
+        .map_err(|why| N4Error::io(&config_dir, why))?;
+    println!("Default config file created.");
+    Ok(())
 }
 
-/// Generate a simple robots.txt file
-pub fn generate_robot_food() -> String {
-    let config = load_config();
-    format!(
-        "User-agents: *
-Allow: *
-
-Sitemap: {}/sitemap.xml",
-        config.prod_host
-    )
+/// One site's worth of content, built once from a SiteConfig and used for every read against that content tree.
+///
+/// Nothing in here goes back to the user config file, so a single process can serve several sites and tests can
+/// point a Site at a temporary directory.
+#[derive(Debug, Clone)]
+pub struct Site {
+    config: SiteConfig,
+    // Loaded the first time a page is rendered and shared by the clones
+    templates: Arc<OnceLock<Templates>>,
+    // The wiki index single page reads share, rebuilt when the tree changes
+    wiki: Arc<SharedWiki>,
 }
 
-// This really just breaks out the file read and JSON deserialize into it's own function
-pub fn read_menu_meta_file(file_path: PathBuf) -> MenuItemMeta {
-    let mut content = String::new();
-
-    // File read
-    let mut _file = match fs::File::open(&file_path) {
-        Err(why) => panic!("Couldn't open file: {}", why),
-        Ok(mut _file) => _file.read_to_string(&mut content),
-    };
-    // Deserialize the JSON
-    let return_struct: MenuItemMeta = match serde_json::from_str(&content) {
-        Err(why) => {
-            println!("Bad menu meta JSON: {} \n {:#?}", why, content); // TODO Change to logging
-            return MenuItemMeta::default();
+impl Site {
+    pub fn new(config: SiteConfig) -> Self {
+        Site {
+            config,
+            templates: Arc::new(OnceLock::new()),
+            wiki: Arc::new(Mutex::new(None)),
         }
-        Ok(value) => value,
-    };
-    return_struct
-}
+    }
 
-// Formats a path to a directory for the .menu_meta extension and checks if it exists
-pub fn add_menu_metadata(meta_path_raw: &String) -> MenuItemMeta {
-    let meta_path: PathBuf = PathBuf::from(&format!("{}{}", meta_path_raw, ".menu_meta"));
+    /// Builds a Site from the standard user config file, mostly for the CLI
+    pub fn load() -> N4Result<Self> {
+        Ok(Site::new(load_config()?))
+    }
 
-    if meta_path.exists() {
-        read_menu_meta_file(meta_path)
-    } else {
-        return MenuItemMeta::default();
+    pub fn config(&self) -> &SiteConfig {
+        &self.config
     }
-}
 
-pub fn tree_to_menus(dir_tree: DirTree) -> HashMap<String, MenuItem> {
-    let mut menus: HashMap<String, MenuItem> = HashMap::new();
-    let config = load_config();
-    let prefix_to_strip = match config.base_dir.strip_suffix("/") {
-        Some(val) => val,
-        _ => panic!("Base dir is missing the trailing directory delimiter."),
-    };
-    for (key, value) in dir_tree.directories {
-        if value.directories.len() > 0 {
+    /// Absolute path to the content root, local_content_dir + base_dir
+    pub fn local_path(&self) -> String {
+        self.config.local_path()
+    }
+
+    /// Menu items for every directory in the tree, keyed by directory name with their section meta and children
+    pub fn tree_to_menus(&self, dir_tree: DirTree) -> N4Result<HashMap<String, MenuItem>> {
+        self.menus_from_tree(&dir_tree, self, &|dir: &DirTree| {
+            add_menu_metadata(&dir.absolute_path)
+        })
+    }
+
+    /// tree_to_menus with the pages and section metadata coming from somewhere other than the filesystem, like the
+    /// cache.  A section whose index page isn't listed, a draft say, is left out with everything under it.  That only
+    /// goes for the menus, the pages under it are still listed, in the sitemap and exported, by their own metas.
+    pub(crate) fn menus_from_tree(
+        &self,
+        dir_tree: &DirTree,
+        source: &dyn PageSource,
+        menu_meta: &dyn Fn(&DirTree) -> N4Result<MenuItemMeta>,
+    ) -> N4Result<HashMap<String, MenuItem>> {
+        let mut menus: HashMap<String, MenuItem> = HashMap::new();
+        let prefix_to_strip = match self.config.base_dir.strip_suffix('/') {
+            Some(val) => val,
+            _ => {
+                return Err(N4Error::InvalidConfig(String::from(
+                    "Base dir is missing the trailing directory delimiter.",
+                )))
+            }
+        };
+        for (key, value) in &dir_tree.directories {
+            let index = self.dir_web_path(value)?.join("index")?;
+            if source.has_content(&index) {
+                match source.page_parts(&index) {
+                    Ok(page) if !self.config.is_listed(&page.meta) => continue,
+                    Ok(_) => {}
+                    Err(why) => println!("Menu failure. {}", why),
+                }
+            }
+            let relative_path = value
+                .relative_path
+                .strip_prefix(prefix_to_strip)
+                .unwrap_or(&value.relative_path)
+                .to_string();
+            let children = if !value.directories.is_empty() {
+                self.menus_from_tree(value, source, menu_meta)? // Recursion
+            } else {
+                HashMap::new() // Blank default
+            };
+            // A broken .menu_meta shouldn't take every menu down, the section just gets the defaults
+            let section_meta = match menu_meta(value) {
+                Ok(val) => val,
+                Err(why) => {
+                    println!("Menu failure. {}", why);
+                    MenuItemMeta::default()
+                }
+            };
             menus.insert(
-                key,
+                key.clone(),
                 MenuItem {
-                    menu_meta: add_menu_metadata(&value.absolute_path),
+                    menu_meta: section_meta,
                     number_of_files: value.files.len() as u32,
-                    relative_path: value
-                        .relative_path
-                        .strip_prefix(prefix_to_strip)
-                        .unwrap()
-                        .to_string(),
-                    children: tree_to_menus(value), // Recursion
+                    relative_path,
+                    children,
                 },
             );
-        } else {
-            menus.insert(
-                key,
-                MenuItem {
-                    menu_meta: add_menu_metadata(&value.absolute_path),
-                    number_of_files: value.files.len() as u32,
-                    relative_path: value
-                        .relative_path
-                        .strip_prefix(prefix_to_strip)
-                        .unwrap()
-                        .to_string(),
-                    children: HashMap::new(), // Blank default
+        }
+        Ok(menus)
+    }
+
+    /// One sitemap entry per logical content item in the tree, hidden files and directories left out as well as
+    /// noindex pages, pages that aren't listed and anything with sitemap_exclude set on the page or a section above it.
+    /// The URL comes from the item's ContentMeta.path and lastmod is ContentMeta.updated or else the newest of its md,
+    /// html and json files.
+    pub(crate) fn tree_to_sitemap(&self, dir_tree: &DirTree) -> N4Result<Vec<SiteMapEntry>> {
+        let mut entries: Vec<SiteMapEntry> = Vec::new();
+        for web_path in self.content_items(dir_tree)? {
+            if web_path.is_hidden() {
+                continue;
+            }
+            if let Some(entry) = self.sitemap_entry(&web_path)? {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn sitemap_entry(&self, web_path: &WebPath) -> N4Result<Option<SiteMapEntry>> {
+        let mut lastmod = unix_time_to_iso(0.0);
+        for extension in ["md", "html", "json"].iter() {
+            let this_path = self.checked_path(web_path, Some(extension))?;
+            if this_path.is_file() {
+                lastmod = lastmod.max(read_file_modified_time(&this_path)?);
+            }
+        }
+        // A broken meta file shouldn't take the whole sitemap down, the item's own path is right anyway.  Only peeked,
+        // generating a sitemap doesn't write default metas into the content tree.
+        let meta = match self.peek_content_meta(&self.checked_path(web_path, None)?) {
+            Ok(meta) => meta,
+            Err(why) => {
+                println!("Sitemap meta failure. {}", why);
+                ContentMeta {
+                    path: web_path.to_string(),
+                    ..ContentMeta::default()
+                }
+            }
+        };
+        Ok(self.sitemap_entry_from(
+            web_path,
+            &meta,
+            lastmod,
+            &self.ancestor_section_metas(web_path),
+        ))
+    }
+
+    /// The sitemap entry for a page from its meta, the newest time of its files and its section metas nearest first,
+    /// None when it's left out of the sitemap
+    pub(crate) fn sitemap_entry_from(
+        &self,
+        web_path: &WebPath,
+        meta: &ContentMeta,
+        mut lastmod: DateTime<Utc>,
+        section_metas: &[MenuItemMeta],
+    ) -> Option<SiteMapEntry> {
+        let location = content_location(web_path, meta);
+
+        // SET SITEMAP VALUES, the page wins then the nearest section that sets one
+        let mut priority = meta.sitemap_priority;
+        let mut changefreq = meta.sitemap_changefreq;
+        let mut exclude = meta.sitemap_exclude;
+        for section_meta in section_metas {
+            priority = priority.or(section_meta.sitemap_priority);
+            changefreq = changefreq.or(section_meta.sitemap_changefreq);
+            exclude = exclude.or(section_meta.sitemap_exclude);
+        }
+        // A noindex page is disallowed in robots.txt, listing it here would just contradict that
+        if exclude == Some(true) || meta.noindex || !self.config.is_listed(meta) {
+            return None;
+        }
+        if let Some(updated) = meta.updated {
+            lastmod = updated;
+        }
+
+        Some(SiteMapEntry {
+            // Percent-encoded here, the XML writer does the escaping
+            location: self.config.absolute_url(&sitemap::url_encode(&location)),
+            lastmod,
+            priority: match priority {
+                Some(val) => val.to_string(),
+                None => self.config.xml_priority.clone(),
+            },
+            changefreq,
+        })
+    }
+
+    /// Section metas from the content's own directory up to the top, directories without a .menu_meta are skipped
+    pub(crate) fn ancestor_section_metas(&self, web_path: &WebPath) -> Vec<MenuItemMeta> {
+        let mut metas: Vec<MenuItemMeta> = Vec::new();
+        let mut section = web_path.parent();
+        while !section.is_root() {
+            match self.checked_path(&section, Some("menu_meta")) {
+                Ok(this_path) if this_path.exists() => match read_menu_meta_file(this_path) {
+                    Ok(val) => metas.push(val),
+                    Err(why) => println!("Section meta failure. {}", why),
                 },
-            );
+                Ok(_) => {}
+                Err(why) => println!("Section meta failure. {}", why),
+            }
+            section = section.parent();
         }
+        metas
     }
-    menus
-}
 
-// Oh the things we do to get the correct ISO timestamps
-pub fn unix_time_to_iso(timestamp: f64) -> chrono::DateTime<chrono::Utc> {
-    let converted_timestamp: i64 = timestamp as i64;
-    let naive_datetime = NaiveDateTime::from_timestamp(converted_timestamp, 0);
-    let datetime_again: DateTime<Utc> = DateTime::from_utc(naive_datetime, Utc);
-    datetime_again
-}
-
-fn tree_to_sitemap(dir_tree: DirTree) -> Vec<SiteMapEntry> {
-    let config = load_config();
-    let mut files: Vec<SiteMapEntry> = Vec::new();
-
-    if dir_tree.files.len() > 0 {
-        for filename in dir_tree.files.keys() {
-            // Strip leading dir in relative path
-            let mut stripped_relative_path = String::new();
-            if dir_tree.relative_path.ends_with("/") {
-                let temp_base_dir = &config.base_dir.strip_suffix("/").unwrap();
-                stripped_relative_path = String::from(
-                    escape(
-                        dir_tree
-                            .relative_path
-                            .strip_prefix(temp_base_dir)
-                            .unwrap_or(""),
-                    )
-                    .to_string(),
-                );
-            } else {
-                stripped_relative_path = String::from(
-                    escape(
-                        dir_tree
-                            .relative_path
-                            .strip_prefix(&config.base_dir)
-                            .unwrap_or(""),
-                    )
-                    .to_string(),
-                );
+    pub fn generate_sitemap(&self) -> N4Result<Vec<SiteMapEntry>> {
+        let dir_tree = self.generate_content_state()?;
+        self.tree_to_sitemap(&dir_tree)
+    }
+
+    pub fn generate_content_state(&self) -> N4Result<file_tree::DirTree> {
+        file_tree::dir_to_tree(&self.local_path(), "")
+    }
+
+    /// Every logical content item in a tree, one web path per file stem with markdown, HTML or JSON content, so
+    /// meta files and the several formats of one page only show up once.  Sorted for stable output.
+    pub fn content_items(&self, dir_tree: &DirTree) -> N4Result<Vec<WebPath>> {
+        let mut items: Vec<WebPath> = Vec::new();
+        let dir_web_path = self.dir_web_path(dir_tree)?;
+        for stem in dir_tree.files.keys() {
+            let web_path = dir_web_path.join(stem)?;
+            if self.does_content_exist(&web_path.to_string()) {
+                items.push(web_path);
             }
-            if &stripped_relative_path.len() > &0 {
-                files.push(SiteMapEntry {
-                    location: format!(
-                        "{}/{}/{}",
-                        config.prod_host, stripped_relative_path, filename
-                    ),
-                    lastmod: unix_time_to_iso(dir_tree.files[filename].modified),
-                    priority: config.xml_priority.clone(),
-                });
-            } else {
-                files.push(SiteMapEntry {
-                    location: format!("{}/{}", config.prod_host, filename),
-                    lastmod: unix_time_to_iso(dir_tree.files[filename].modified),
-                    priority: config.xml_priority.clone(),
-                });
+        }
+        for subdir in dir_tree.directories.values() {
+            items.append(&mut self.content_items(subdir)?);
+        }
+        items.sort();
+        Ok(items)
+    }
+
+    /// Web path of a directory in a DirTree built from this site
+    pub fn dir_web_path(&self, dir_tree: &DirTree) -> N4Result<WebPath> {
+        match Path::new(&dir_tree.absolute_path).strip_prefix(self.local_path()) {
+            Ok(val) => WebPath::parse(&val.to_string_lossy()),
+            Err(_) => Err(N4Error::PathEscape(dir_tree.absolute_path.clone())),
+        }
+    }
+
+    /// The metas of the pages in a directory in listing order.  Only a directory that can't be read is an Err, an
+    /// entry that can't be, bad meta or a symlink out of the content root say, is logged and left out.
+    // TODO Rename this function to something clearer
+    pub fn read_full_dir_sorted(&self, web_path_dir: &str) -> N4Result<Vec<ContentMeta>> {
+        let dir_web_path = WebPath::parse(web_path_dir)?;
+        let local_path = self.checked_path(&dir_web_path, None)?;
+        let paths = fs::read_dir(&local_path).map_err(|why| N4Error::io(&local_path, why))?;
+        let mut page_metas: Vec<(String, ContentMeta)> = Vec::new();
+        let mut entries_read: Vec<String> = Vec::new(); // We just need one metafile read per content file track it here
+        for dir_entry in paths {
+            let check_path = match dir_entry {
+                Ok(val) => val.path(),
+                Err(why) => {
+                    println!(
+                        "Directory listing failure. {}",
+                        N4Error::io(&local_path, why)
+                    );
+                    continue;
+                }
+            };
+            if !check_path.is_dir() && !check_path.to_string_lossy().ends_with("meta") {
+                let this_stem = string_from_stem(&check_path);
+                // If we already read it, it's in the entries Vec so skip
+                if !entries_read.contains(&this_stem) {
+                    entries_read.push(this_stem.clone());
+                    // read_content_meta opens the sidecar and the markdown, both have to stay inside the root
+                    let meta = dir_web_path.join(&this_stem).and_then(|x| {
+                        self.checked_path(&x, Some("md"))?;
+                        self.read_content_meta(&self.checked_path(&x, Some("content_meta"))?)
+                    });
+                    match meta {
+                        Ok(meta) if self.config.is_listed(&meta) => {
+                            page_metas.push((this_stem, meta))
+                        }
+                        Ok(_) => {}
+                        Err(why) => println!("Directory listing failure. {}", why),
+                    }
+                }
+            }
+        }
+        match self.section_meta_of(&dir_web_path)?.listing {
+            ListingMode::Weight => page_metas.sort_unstable_by_key(|x| x.1.weight),
+            // The posts in blog_posts order, newest by page_date first and without the index page
+            ListingMode::Blog => {
+                let posts = self.blog_posts(web_path_dir)?;
+                let mut by_stem: HashMap<String, ContentMeta> = page_metas.into_iter().collect();
+                page_metas = posts
+                    .iter()
+                    .filter_map(|x| x.web_path.file_name())
+                    .filter_map(|x| by_stem.remove_entry(x))
+                    .collect();
+            }
+        }
+        Ok(page_metas.into_iter().map(|x| x.1).collect())
+    }
+
+    // Mainly for reading the content_meta content_list values prefixes local dir and document base dir
+    pub fn read_content_list(&self, list_o_content: &[String]) -> N4Result<Vec<PageContent>> {
+        assemble_content_list(self, list_o_content, &mut Vec::new())
+    }
+
+    /// This is a compositional function to pull the parts together into a page.  Each component load also breaks
+    /// down further into file system operations, parsing and such.
+    ///
+    /// Parameters:
+    ///     this_path(&str), a web path most likely delivered by the web server routing
+    /// Returns:
+    ///     PageContent, struct containing all the pieces of a content page, or N4Error::NotFound if there is no
+    ///     markdown, HTML or JSON content at that path
+    pub fn read_single_page(&self, this_path: &str) -> N4Result<PageContent> {
+        assemble_page(self, WebPath::parse(this_path)?, &mut Vec::new())
+    }
+
+    /// Take a local path and read the content meta if it has it.
+    ///
+    /// NOTE: Unlike the other simple readers, this one will create a default, customize it a bit and save it
+    /// if the metafile doesn't exist so our page can still render somewhat correctly and we can modify the
+    /// values manually.  Markdown with front matter is the exception, the front matter is the meta then and
+    /// nothing gets written.  With both, front matter fields override the sidecar's.  In read_only mode the
+    /// default is never saved, see scaffold_missing_meta for writing them on purpose.
+    ///
+    /// Parameters:
+    ///     this_path(&Path), the absolute path in the filesystem for the content, any extension is replaced
+    /// Returns:
+    ///     ContentMeta, The metafile struct for content
+    pub fn read_content_meta(&self, this_path: &Path) -> N4Result<ContentMeta> {
+        self.content_meta_from_disk(this_path, !self.config.read_only)
+    }
+
+    /// read_content_meta that never saves a default, whatever read_only says, for reads that only look at the meta
+    pub(crate) fn peek_content_meta(&self, this_path: &Path) -> N4Result<ContentMeta> {
+        self.content_meta_from_disk(this_path, false)
+    }
+
+    fn content_meta_from_disk(
+        &self,
+        this_path: &Path,
+        save_default: bool,
+    ) -> N4Result<ContentMeta> {
+        let markdown_path = this_path.with_extension("md");
+        let front_matter = if markdown_path.is_file() {
+            front_matter_value(&markdown_path, &read_file_to_string(&markdown_path)?)?
+        } else {
+            None
+        };
+        self.content_meta_with(this_path, front_matter, save_default)
+    }
+
+    // read_content_meta with the front matter already pulled out of the markdown, so a page read opens its .md once
+    fn content_meta_with(
+        &self,
+        this_path: &Path,
+        front_matter: Option<Value>,
+        save_default: bool,
+    ) -> N4Result<ContentMeta> {
+        let this_path = this_path.with_extension("content_meta");
+        let markdown_path = this_path.with_extension("md");
+        let front_matter = match front_matter {
+            Some(val) => val,
+            None if this_path.exists() => return read_content_meta_file(this_path),
+            None => {
+                let new_meta = self.default_content_meta(&this_path)?;
+                if save_default {
+                    // The read still works without the file, so failing to save it isn't fatal
+                    if let Err(why) = save_content_meta_file(&this_path, &new_meta) {
+                        println!("Content meta failure. Default not saved: {}", why);
+                    }
+                }
+                return Ok(new_meta);
+            }
+        };
+
+        // SET FRONT MATTER over the sidecar, or over the defaults when there isn't one
+        let base = if this_path.exists() {
+            read_content_meta_file(this_path)?
+        } else {
+            self.default_content_meta(&this_path)?
+        };
+        let mut merged = serde_json::to_value(base).map_err(N4Error::Serialize)?;
+        if let (Some(target), Value::Object(fields)) = (merged.as_object_mut(), front_matter) {
+            for (key, value) in fields {
+                target.insert(key, value);
             }
         }
+        serde_json::from_value(merged).map_err(|why| N4Error::MetaParse {
+            path: markdown_path,
+            source: why,
+        })
     }
-    if dir_tree.directories.len() > 0 {
-        for _dir_tree in dir_tree.directories {
-            files.append(&mut tree_to_sitemap(_dir_tree.1));
+
+    /// Writes a default .content_meta for every content item that has neither a sidecar nor front matter, this
+    /// works in read_only mode too since it's asked for explicitly.
+    ///
+    /// Returns:
+    ///     Vec<PathBuf>, the meta files written, sorted
+    pub fn scaffold_missing_meta(&self) -> N4Result<Vec<PathBuf>> {
+        let mut written: Vec<PathBuf> = Vec::new();
+        for web_path in self.content_items(&self.generate_content_state()?)? {
+            let meta_path = self.checked_path(&web_path, Some("content_meta"))?;
+            if meta_path.exists() {
+                continue;
+            }
+            let markdown_path = meta_path.with_extension("md");
+            if markdown_path.is_file()
+                && front_matter_value(&markdown_path, &read_file_to_string(&markdown_path)?)?
+                    .is_some()
+            {
+                continue;
+            }
+            save_content_meta_file(&meta_path, &self.default_content_meta(&meta_path)?)?;
+            written.push(meta_path);
         }
+        written.sort();
+        Ok(written)
     }
 
-    files
-}
+    // What a page gets when nobody wrote any meta for it
+    fn default_content_meta(&self, this_path: &Path) -> N4Result<ContentMeta> {
+        Ok(ContentMeta {
+            title: string_from_stem(this_path),
+            path: self.localpath_to_webpath(this_path)?,
+            ..ContentMeta::default()
+        })
+    }
 
-pub fn generate_sitemap() -> Vec<SiteMapEntry> {
-    let config = load_config();
-    let dir_tree = file_tree::dir_to_tree(&config.local_path(), "");
+    // For a given piece of content (by web path) pull the directory menu_meta file as section meta or return a default
+    pub fn read_section_meta(&self, content_location: &str) -> N4Result<MenuItemMeta> {
+        self.section_meta_of(&WebPath::parse(content_location)?.parent())
+    }
 
-    let sitemap = tree_to_sitemap(dir_tree);
+    /// A directory's .menu_meta, the defaults for the root or a directory without one
+    pub(crate) fn section_meta_of(&self, section: &WebPath) -> N4Result<MenuItemMeta> {
+        if section.is_root() {
+            return Ok(MenuItemMeta::default());
+        }
+        let this_path = self.checked_path(section, Some("menu_meta"))?;
+        if this_path.exists() {
+            read_menu_meta_file(this_path)
+        } else {
+            Ok(MenuItemMeta::default())
+        }
+    }
 
-    return sitemap;
+    pub fn localpath_to_webpath(&self, this_localpath: &Path) -> N4Result<String> {
+        let mut extensionless_path = this_localpath.to_path_buf();
+        extensionless_path.set_extension("");
+        // Component wise strip so doubled delimiters in the configured paths don't leak into the web path
+        let rel_path = match extensionless_path.strip_prefix(self.local_path()) {
+            Ok(val) => val.to_string_lossy().to_string(),
+            Err(_) => {
+                return Err(N4Error::PathEscape(
+                    extensionless_path.to_string_lossy().to_string(),
+                ))
+            }
+        };
+        Ok(WebPath::parse(&rel_path)?.to_string())
+    }
+
+    /// Validates a web path and maps it under the content root, no extension is added
+    pub fn webpath_to_localpath(&self, this_webpath: &str) -> N4Result<PathBuf> {
+        Ok(WebPath::parse(this_webpath)?.to_local(Path::new(&self.local_path())))
+    }
+
+    /// The local path for a web path with an optional extension.  If that file exists it has to resolve inside the
+    /// content root after following symlinks, otherwise it's an N4Error::PathEscape.  A missing file is returned
+    /// as is so callers can decide what absence means.
+    pub fn checked_path(&self, web_path: &WebPath, extension: Option<&str>) -> N4Result<PathBuf> {
+        let root = Path::new(&self.local_path()).to_path_buf();
+        let mut local = web_path.to_local(&root);
+        if let Some(extension) = extension {
+            local.set_extension(extension);
+        }
+        if fs::symlink_metadata(&local).is_ok() {
+            let canonical_root = fs::canonicalize(&root).map_err(|why| N4Error::io(&root, why))?;
+            // A dangling symlink fails canonicalize, treat it like an escape rather than a missing file
+            let inside = match fs::canonicalize(&local) {
+                Ok(canonical) => canonical.starts_with(&canonical_root),
+                Err(_) => false,
+            };
+            if !inside {
+                return Err(N4Error::PathEscape(web_path.to_string()));
+            }
+        }
+        Ok(local)
+    }
+
+    /// Checks a given webpath to see if the base content exists in one of the three formats by extension
+    ///
+    /// Parameters:
+    ///     potential_content_webpath (&str), should be a web renderable path
+    /// Returns:
+    ///     bool, does it exist?  Paths that fail validation or escape the content root never exist.
+    pub fn does_content_exist(&self, potential_content_webpath: &str) -> bool {
+        let web_path = match WebPath::parse(potential_content_webpath) {
+            Ok(val) => val,
+            Err(_) => return false,
+        };
+        ["md", "html", "json"].iter().any(|extension| {
+            match self.checked_path(&web_path, Some(extension)) {
+                Ok(this_path) => this_path.is_file(),
+                Err(_) => false,
+            }
+        })
+    }
+
+    pub fn does_directory_exist(&self, potential_content_webpath: &str) -> bool {
+        // Maybe a good place for a directory blacklist?
+        match WebPath::parse(potential_content_webpath) {
+            Ok(web_path) => match self.checked_path(&web_path, None) {
+                Ok(this_path) => this_path.is_dir(),
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
 }
 
-pub fn generate_content_state() -> file_tree::DirTree {
-    let config = load_config();
-    let dir_tree = file_tree::dir_to_tree(&config.local_path(), "");
-    dir_tree
+impl PageSource for Site {
+    fn site_config(&self) -> &SiteConfig {
+        &self.config
+    }
+
+    fn has_content(&self, web_path: &WebPath) -> bool {
+        self.does_content_exist(&web_path.to_string())
+    }
+
+    fn page_parts(&self, web_path: &WebPath) -> N4Result<PageContent> {
+        self.read_page_parts(web_path, &|| self.shared_wiki_index())
+    }
 }
 
-// TODO Rename this function to something clearer
-pub fn read_full_dir_sorted(web_path_dir: String) -> Vec<ContentMeta> {
-    let local_path = webpath_to_localpath(web_path_dir);
-    let paths = match fs::read_dir(&local_path) {
-        Err(why) => panic!("Dir exists but can't be read: {}", why),
-        Ok(val) => val,
-    };
-    let mut page_metas: Vec<ContentMeta> = Vec::new();
-    let mut entries_read: Vec<String> = Vec::new(); // We just need one metafile read per content file track it here
-    for dir_entry in paths {
-        let check_path = match &dir_entry {
-            Err(why) => panic!("Well this was an unexpected entry in a dir: {}", why),
-            Ok(val) => val.path(),
+impl Site {
+    /// page_parts with where to get the wiki index to link against, only called if the page needs it
+    pub(crate) fn read_page_parts(
+        &self,
+        web_path: &WebPath,
+        wiki: &dyn Fn() -> N4Result<Arc<WikiIndex>>,
+    ) -> N4Result<PageContent> {
+        // SET SECTION META
+        let section_meta = self.read_section_meta(&web_path.to_string())?;
+        // READ MARKDOWN once, the front matter goes into the meta and the body into the MDContent
+        let markdown_path = self.checked_path(web_path, Some("md"))?;
+        let markdown_file = if markdown_path.exists() {
+            Some(read_file_to_string(&markdown_path)?)
+        } else {
+            None
         };
-        let this_path = &check_path.to_string_lossy().to_string();
-        if !&check_path.is_dir() && !this_path.ends_with("meta") {
-            if !entries_read.iter().any(|x| {
-                // If we already read it, it's in the entries Vec so skip
-                x == &check_path
-                    .file_stem()
-                    .unwrap()
-                    .to_string_lossy()
-                    .to_string()
-            }) {
-                entries_read.push(
-                    check_path
-                        .file_stem()
-                        .unwrap()
-                        .to_string_lossy()
-                        .to_string(),
-                );
-                page_metas.push(read_content_meta(&this_path));
+        let (front_matter, markdown_body) = match &markdown_file {
+            Some(val) => {
+                let (front_matter, body) = parse_front_matter(&markdown_path, val)?;
+                (front_matter, Some(body))
             }
+            None => (None, None),
+        };
+        // SET CONTENT META
+        let meta = self.content_meta_with(
+            &self.checked_path(web_path, Some("content_meta"))?,
+            front_matter,
+            !self.config.read_only,
+        )?;
+        let markdown_options = self
+            .config
+            .markdown
+            .with(&section_meta.markdown)
+            .with(&meta.markdown);
+        let sanitize = if self.config.sanitize.enabled && !section_meta.trusted {
+            Some(&self.config.sanitize)
+        } else {
+            None
+        };
+        // SET MARKDOWN CONTENT
+        let mut markdown =
+            read_markdown_content(&markdown_path, markdown_body, &markdown_options, sanitize)?;
+        // SET WIKI LINK INDEX
+        if markdown_options.wiki_links && markdown.source.contains("[[") {
+            markdown.wiki = Some(wiki()?);
         }
+        Ok(PageContent {
+            web_path: web_path.clone(),
+            // SET TABLE OF CONTENTS
+            toc: if markdown_options.toc {
+                markdown_toc(&markdown.source, &markdown_options)
+            } else {
+                Vec::new()
+            },
+            markdown,
+            // SET HTML CONTENT
+            html: read_html_content(&self.checked_path(web_path, Some("html"))?, sanitize)?,
+            section_meta,
+            meta,
+            // SET JSON CONTENT
+            json: read_json_content(&self.checked_path(web_path, Some("json"))?)?,
+            list: Vec::new(),
+        })
+    }
+}
+
+/// Where the pieces of a page come from.  Site goes to the filesystem every time and ContentIndex to its cache,
+/// both share the content_list assembly below.
+pub(crate) trait PageSource {
+    fn site_config(&self) -> &SiteConfig;
+    fn has_content(&self, web_path: &WebPath) -> bool;
+    /// Everything for the page except the pages from its content_list
+    fn page_parts(&self, web_path: &WebPath) -> N4Result<PageContent>;
+}
+
+pub(crate) fn assemble_page(
+    source: &dyn PageSource,
+    web_path: WebPath,
+    chain: &mut Vec<WebPath>,
+) -> N4Result<PageContent> {
+    if !source.has_content(&web_path) {
+        return Err(N4Error::NotFound(web_path.to_string()));
+    }
+    let mut page_content = source.page_parts(&web_path)?;
+
+    // If the meta file contains a content_list of web paths, load the content from that list
+    // into the PageContent.list Vec.  The chain guards the recursion against circular references.
+    if !page_content.meta.content_list.is_empty() {
+        chain.push(web_path);
+        let list = assemble_content_list(source, &page_content.meta.content_list, chain);
+        chain.pop();
+        page_content.list = list?;
     }
-    page_metas.sort_unstable_by_key(|x| x.weight);
-    page_metas
+
+    Ok(page_content)
 }
 
-// Mainly for reading the content_meta content_list values prefixes local dir and document base dir
-pub fn read_content_list(list_o_content: &Vec<String>) -> Vec<PageContent> {
+/// Loads the pages of a content_list with the chain of pages currently being loaded.  Entries already in the chain
+/// would be a circular reference and entries past content_list_max_depth would nest too far, both are left out of
+/// the list the same way missing content is.  Pages that aren't listed, drafts and the like, are quietly left out.
+pub(crate) fn assemble_content_list(
+    source: &dyn PageSource,
+    list_o_content: &[String],
+    chain: &mut Vec<WebPath>,
+) -> N4Result<Vec<PageContent>> {
+    let max_depth = source.site_config().content_list_max_depth;
     let mut page_list: Vec<PageContent> = Vec::new();
     for item in list_o_content {
-        if does_content_exist(item.clone()) {
-            page_list.push(read_single_page(item.clone()));
+        let web_path = match WebPath::parse(item) {
+            Ok(val) if source.has_content(&val) => val,
+            _ => {
+                println!("Content list failure.  This doesn't exist: {}", item);
+                continue;
+            }
+        };
+        if chain.contains(&web_path) {
+            println!(
+                "Content list failure.  Circular reference to {} from {}",
+                web_path,
+                display_chain(chain)
+            );
+        } else if chain.len() > max_depth {
+            println!(
+                "Content list failure.  Max depth of {} reached at {}",
+                max_depth,
+                display_chain(chain)
+            );
         } else {
-            println!("Content list failure.  This doesn't exist: {}", item);
+            let page = assemble_page(source, web_path, chain)?;
+            if source.site_config().is_listed(&page.meta) {
+                page_list.push(page);
+            }
         }
     }
 
     page_list.sort_unstable_by_key(|x| x.meta.weight);
-    page_list
+    Ok(page_list)
 }
 
-/// This is a compositional function to pull the parts together into a page.  Each component load also breaks down
-/// further into file system operations, parsing and such.
-///
-/// Parameters:
-///     this_path(String), a web path most likely delivered by the web server routing
-/// Returns:
-///     PageContent, struct containing all the pieces of a content page
-pub fn read_single_page(this_path: String) -> PageContent {
-    let config = load_config();
-
-    let full_path_string = format!("{}{}", config.local_path(), &this_path);
-    let mut page_content: PageContent = PageContent::default();
-
-    // SET SECTION META
-    page_content.section_meta = read_section_meta(&full_path_string);
-    // SET CONTENT META
-    page_content.meta = read_content_meta(&full_path_string);
-    // SET MARKDOWN CONTENT
-    page_content.markdown = read_markdown_content(&full_path_string);
-    // SET HTML CONTENT
-    page_content.html = read_html_content(&full_path_string);
-    // SET JSON CONTENT
-    page_content.json = read_json_content(&full_path_string);
+fn display_chain(chain: &[WebPath]) -> String {
+    chain
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(" -> ")
+}
 
-    // If the meta file contains a content_list of web paths, load the content from that list
-    // into the PageContent.list Vec.
-    // NOTE: This is recursive in an unsafe way, a circular reference will break things here
-    if page_content.meta.content_list.len() > 0 {
-        page_content.list = read_content_list(&page_content.meta.content_list);
+// This really just breaks out the file read and JSON deserialize into it's own function
+pub fn read_menu_meta_file(file_path: PathBuf) -> N4Result<MenuItemMeta> {
+    let content = read_file_to_string(&file_path)?;
+    // Deserialize the JSON
+    serde_json::from_str(&content).map_err(|why| N4Error::MetaParse {
+        path: file_path,
+        source: why,
+    })
+}
+
+// Formats a path to a directory for the .menu_meta extension and checks if it exists
+pub fn add_menu_metadata(meta_path_raw: &str) -> N4Result<MenuItemMeta> {
+    let meta_path: PathBuf = PathBuf::from(&format!("{}{}", meta_path_raw, ".menu_meta"));
+
+    if meta_path.exists() {
+        read_menu_meta_file(meta_path)
+    } else {
+        Ok(MenuItemMeta::default())
     }
+}
 
-    page_content
+// Oh the things we do to get the correct ISO timestamps
+pub fn unix_time_to_iso(timestamp: f64) -> chrono::DateTime<chrono::Utc> {
+    let converted_timestamp: i64 = timestamp as i64;
+    DateTime::from_timestamp(converted_timestamp, 0).unwrap_or_default()
 }
 
-/// Take a String turn it into a pathbuf and read the content meta if it has it.
-///
-/// NOTE: Unlike the other simple readers, this one will create a default, customize it a bit and save it
-/// if the metafile doesn't exist so our page can still render somewhat correctly and we can modify the
-/// values manually.
-///
-/// Parameters:
-///     full_path_string(&String), the absolute path in the filesystem for the metafile
-/// Returns:
-///     ContentMeta, The metafile struct for content
-pub fn read_content_meta(full_path_string: &String) -> ContentMeta {
-    let mut this_path = PathBuf::from(full_path_string);
-    this_path.set_extension("content_meta");
-    if this_path.exists() {
-        let this_content_meta = read_content_meta_file(this_path);
-        return this_content_meta;
+// The body is the markdown already read with its front matter stripped, None when there's no markdown file
+fn read_markdown_content(
+    markdown_path: &Path,
+    body: Option<&str>,
+    options: &MarkdownOptions,
+    sanitize: Option<&SanitizeConfig>,
+) -> N4Result<MDContent> {
+    if let Some(body) = body {
+        Ok(MDContent {
+            created: read_file_creation_time(markdown_path)?,
+            modified: read_file_modified_time(markdown_path)?,
+            source: body.to_string(),
+            rendered: None,
+            options: options.clone(),
+            sanitize: sanitize.cloned(),
+            wiki: None,
+        })
     } else {
-        let mut new_meta = ContentMeta::default();
-        new_meta.title = string_from_stem(&this_path);
-        new_meta.path = localpath_to_webpath(&this_path);
-        save_content_meta_file(&this_path, &new_meta);
-        return new_meta;
-    }
-}
-
-fn read_markdown_content(this_path_string: &String) -> MDContent {
-    let mut markdown_path = PathBuf::from(this_path_string);
-    markdown_path.set_extension("md");
-    if markdown_path.exists() {
-        let markdown_content = MDContent {
-            created: read_file_creation_time(&markdown_path),
-            modified: read_file_modified_time(&markdown_path),
-            body: read_markdown_from_path(&markdown_path), //TODO Lint/Validate/Filter here?
-        };
-        return markdown_content;
-    } else {
-        let mut markdown_content = MDContent::default();
-        markdown_content.body = format!(
+        // The local path is for the log only, rendered output ends up in templates and exports
+        println!(
             "Markdown file does not exist: {}",
             markdown_path.to_string_lossy()
         );
-        return markdown_content;
+        Ok(MDContent {
+            source: String::new(),
+            rendered: Some(String::new()),
+            ..MDContent::default()
+        })
     }
 }
 
-fn read_html_content(this_path_string: &String) -> Option<HTMLContent> {
-    let mut html_path = PathBuf::from(this_path_string);
-    html_path.set_extension("html");
+fn read_html_content(
+    html_path: &Path,
+    sanitize: Option<&SanitizeConfig>,
+) -> N4Result<Option<HTMLContent>> {
     if html_path.exists() {
-        let html_content = HTMLContent {
-            created: read_file_creation_time(&html_path),
-            modified: read_file_modified_time(&html_path),
-            body: read_html_from_path(&html_path), //TODO Lint/Validate/Filter here?
-        };
-        return Some(html_content);
+        let body = read_html_from_path(html_path)?;
+        Ok(Some(HTMLContent {
+            created: read_file_creation_time(html_path)?,
+            modified: read_file_modified_time(html_path)?,
+            body: match sanitize {
+                Some(val) => val.clean(&body),
+                None => body,
+            },
+        }))
     } else {
-        // let mut html_content = HTMLContent::default();
-        // html_content.body = format!("HTML file does not exist: {}", html_path.to_string_lossy());
-        // return html_content;
-        return None;
+        Ok(None)
     }
 }
 
-fn read_json_content(this_path_string: &String) -> Option<JSONContent> {
-    let mut json_path = PathBuf::from(this_path_string);
-    json_path.set_extension("json");
+fn read_json_content(json_path: &Path) -> N4Result<Option<JSONContent>> {
     if json_path.exists() {
-        let json_content = JSONContent {
-            created: read_file_creation_time(&json_path),
-            modified: read_file_modified_time(&json_path),
-            body: read_json_from_path(&json_path), //TODO Lint/Validate/Filter here?
-        };
-        return Some(json_content);
+        Ok(Some(JSONContent {
+            created: read_file_creation_time(json_path)?,
+            modified: read_file_modified_time(json_path)?,
+            body: read_json_from_path(json_path)?, // Data rather than markup, the consumer escapes it
+        }))
     } else {
-        // let mut json_content = JSONContent::default();
-        // json_content.body = format!("JSON file does not exist: {}", json_path.to_string_lossy());
-        // return json_content;
-        return None;
+        Ok(None)
     }
 }
 
 /// Just wraps the .filestem() method to always return a string even if it's an error.
-fn string_from_stem(this_path: &PathBuf) -> String {
-    let this_string = match this_path.file_stem() {
+fn string_from_stem(this_path: &Path) -> String {
+    match this_path.file_stem() {
         Some(val) => val.to_string_lossy().to_string(),
         _ => String::from("Default file stem value ERROR."),
-    };
-    this_string
+    }
 }
 
 /// Standard set of filesystem and serialization operations to save a content metafile
-fn save_content_meta_file(this_path: &PathBuf, metadata: &ContentMeta) {
-    let mut file = match fs::File::create(this_path) {
-        Err(why) => panic!("Content meta default file creation fail: {}", why),
-        Ok(value) => value,
-    };
-    let serialized_meta = match serde_json::to_string_pretty(&metadata) {
-        Err(why) => panic!("Serialize to json fail: {}", why),
-        Ok(value) => value,
-    };
-    match file.write_all(&serialized_meta.as_bytes()) {
-        Err(why) => panic!(
-            "A default metafile couldn't be created for this path: {}, {}",
-            this_path.to_string_lossy(),
-            why
-        ),
-        Ok(val) => val,
-    };
+fn save_content_meta_file(this_path: &Path, metadata: &ContentMeta) -> N4Result<()> {
+    let serialized_meta = serde_json::to_string_pretty(&metadata).map_err(N4Error::Serialize)?;
+    let mut file = fs::File::create(this_path).map_err(|why| N4Error::io(this_path, why))?;
+    file.write_all(serialized_meta.as_bytes())
+        .map_err(|why| N4Error::io(this_path, why))
 }
 
 /// File system read and deserialization of a ContentMeta file
-pub fn read_content_meta_file(file_path: PathBuf) -> ContentMeta {
-    let mut content_meta = String::new();
-
-    // File read
-    let mut _file = match fs::File::open(&file_path) {
-        Err(why) => panic!(
-            "Couldn't open content meta file: {} -> {}",
-            &file_path.to_string_lossy(),
-            why
-        ),
-        Ok(mut _file) => _file.read_to_string(&mut content_meta),
-    };
+pub fn read_content_meta_file(file_path: PathBuf) -> N4Result<ContentMeta> {
+    let content_meta = read_file_to_string(&file_path)?;
     // Deserialize the JSON
-    let return_struct: ContentMeta = match serde_json::from_str(&content_meta) {
-        Err(why) => {
-            // TODO This should trigger an integrity check and correct the JSON file with default values if
-            // possible while preserving existing values.
-            let mut error_meta = ContentMeta::default();
-            error_meta.description = format!("JSON Parse Error: {}", why);
-            error_meta.title = "Error parsing metadata file".to_string();
-            error_meta
-        }
-        Ok(value) => value,
-    };
-    return_struct
-}
-
-// For a given piece of content pull the directory menu_meta file as section meta or return a default
-pub fn read_section_meta(content_location: &String) -> MenuItemMeta {
-    let config = load_config();
-    let mut this_path = PathBuf::from(format!("{}{}", config.local_path(), content_location));
-    this_path.pop();
-    this_path.set_extension("menu_meta");
-    if this_path.exists() {
-        let this_menu_meta = read_menu_meta_file(this_path);
-        return this_menu_meta;
-    } else {
-        return MenuItemMeta::default();
-    }
+    // TODO This should trigger an integrity check and correct the JSON file with default values if
+    // possible while preserving existing values.
+    serde_json::from_str(&content_meta).map_err(|why| N4Error::MetaParse {
+        path: file_path,
+        source: why,
+    })
 }
 
-pub fn read_file_creation_time(path: &std::path::Path) -> chrono::DateTime<chrono::Utc> {
-    //NaiveDateTime {
-    let metadata = fs::metadata(path).expect("Not found");
-
-    let _ = match metadata.created() {
-        Err(why) => panic!("Couldn't get file metadata: {}", why),
-        Ok(_time) => {
-            let _temp_time = _time.duration_since(UNIX_EPOCH).unwrap().as_secs() as f64;
-            return unix_time_to_iso(_temp_time); //NaiveDateTime::from_timestamp(_temp_time, 0);
-        }
+/// Birth time of a file, filesystems that don't record one fall back to the modified time
+pub fn read_file_creation_time(path: &std::path::Path) -> N4Result<chrono::DateTime<chrono::Utc>> {
+    let metadata = fs::metadata(path).map_err(|why| N4Error::io(path, why))?;
+    let created = match metadata.created() {
+        Ok(_time) => _time,
+        Err(_) => metadata.modified().map_err(|why| N4Error::io(path, why))?,
     };
+    Ok(system_time_to_iso(created))
 }
 
-pub fn read_file_modified_time(path: &std::path::Path) -> chrono::DateTime<chrono::Utc> {
-    //NaiveDateTime {
-    let metadata = fs::metadata(path).expect("Not found");
+pub fn read_file_modified_time(path: &std::path::Path) -> N4Result<chrono::DateTime<chrono::Utc>> {
+    let metadata = fs::metadata(path).map_err(|why| N4Error::io(path, why))?;
+    let modified = metadata.modified().map_err(|why| N4Error::io(path, why))?;
+    Ok(system_time_to_iso(modified))
+}
 
-    let _ = match metadata.modified() {
-        Err(why) => panic!("Couldn't get file metadata: {}", why), // TODO Remove panic
-        Ok(_time) => {
-            let _temp_time = _time.duration_since(UNIX_EPOCH).unwrap().as_secs() as f64;
-            return unix_time_to_iso(_temp_time); //NaiveDateTime::from_timestamp(_temp_time, 0);
-        }
-    };
-    // unix_time_to_iso()
+fn system_time_to_iso(time: std::time::SystemTime) -> chrono::DateTime<chrono::Utc> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(_temp_time) => unix_time_to_iso(_temp_time.as_secs() as f64),
+        Err(_) => unix_time_to_iso(0.0),
+    }
 }
 
 //
 // INFO Potential section of file system operations to move to a module
 //
 
-fn localpath_to_webpath(this_localpath: &std::path::PathBuf) -> String {
-    let config = load_config();
-    let mut extensionless_path = this_localpath.clone();
-    extensionless_path.set_extension("");
-    let mut rel_path = extensionless_path.to_string_lossy().to_string();
-    // offset is necessary for replace range, this is the calculation of it
-    let offset = rel_path.find(&config.base_dir).unwrap() + config.base_dir.len(); // I think panic here is ok as it will break the site generally anyway to send anything incorrect
-    rel_path.replace_range(..offset, "/");
-    return rel_path;
-}
-
-fn webpath_to_localpath(this_webpath: String) -> String {
-    let config = load_config();
-    let this_local_path = format!("{}{}", config.local_path(), this_webpath);
-    return this_local_path;
-}
-
-// This function looks for a given extension variant for a string of a path
-// TODO Add an input validation layer here, check for illegal escape attempts and return False if found
-// TODO TODO This is probably not even necessary anymore given the PathBuf.set_extension() method now
-pub fn check_path_alternatives(this_path: &String, extension: &str) -> bool {
+// This function looks for a given extension variant for a string of a local path
+// NOTE No validation happens here, anything from the web side should go through WebPath and Site::checked_path
+// TODO This is probably not even necessary anymore given the PathBuf.set_extension() method now
+pub fn check_path_alternatives(this_path: &str, extension: &str) -> bool {
     let mut this_path = PathBuf::from(this_path);
     this_path.set_extension(extension);
     this_path.exists()
 }
 
-/// Checks a given webpath to see if the base content exists in one of the three formats by extension
-///
-/// Parameters:
-///     potential_content_webpath (String), should be a web renderable path
-/// Returns:
-///     bool, does it exist?
-pub fn does_content_exist(potential_content_webpath: String) -> bool {
-    let mut this_path = PathBuf::from(webpath_to_localpath(potential_content_webpath));
-    this_path.set_extension("md");
-    if this_path.exists() {
-        return true;
-    }
-    this_path.set_extension("html");
-    if this_path.exists() {
-        return true;
-    }
-    this_path.set_extension("json");
-    if this_path.exists() {
-        return true;
-    }
-    false
+// TODO The following functions are place holders for the same but with strong validation
+
+fn read_file_to_string(path: &std::path::Path) -> N4Result<String> {
+    let mut content = String::new();
+    let mut _file = fs::File::open(path).map_err(|why| N4Error::io(path, why))?;
+    _file
+        .read_to_string(&mut content)
+        .map_err(|why| N4Error::io(path, why))?;
+    Ok(content)
 }
 
-pub fn does_directory_exist(potential_content_webpath: String) -> bool {
-    // Maybe a good place for a directory blacklist?
-    let this_path = webpath_to_localpath(potential_content_webpath);
-    return Path::new(&this_path).is_dir();
+pub fn read_markdown_from_path(path: &std::path::Path) -> N4Result<String> {
+    read_markdown_with_options(path, &MarkdownOptions::default())
 }
 
-// TODO The following functions are place holders for the same but with strong validation
+pub fn read_markdown_with_options(
+    path: &std::path::Path,
+    options: &MarkdownOptions,
+) -> N4Result<String> {
+    Ok(render_markdown(&read_markdown_source(path)?, options))
+}
 
-pub fn read_markdown_from_path(path: &std::path::Path) -> String {
-    let mut content = String::new();
-    let mut _file = match fs::File::open(&path) {
-        Err(why) => panic!("Couldn't open file: {}", why),
-        Ok(mut _file) => match _file.read_to_string(&mut content) {
-            Err(why) => panic!("Couldn't read file: {}", why),
-            // Ok(_) => return markdown::to_html(&content),
-            Ok(_) => return markdown_to_html(&content, &ComrakOptions::default()),
-        },
-    };
+/// The markdown as written, front matter stripped and not rendered
+pub fn read_markdown_source(path: &std::path::Path) -> N4Result<String> {
+    let content = read_file_to_string(path)?;
+    Ok(strip_front_matter(&content).to_string())
 }
 
-pub fn read_html_from_path(path: &std::path::Path) -> String {
-    let mut content = String::new();
-    let mut _file = match fs::File::open(&path) {
-        Err(why) => panic!("Couldn't open file: {}", why),
-        Ok(mut _file) => match _file.read_to_string(&mut content) {
-            Err(why) => panic!("Couldn't read file: {}", why),
-            Ok(_) => return content,
-        },
-    };
+pub fn read_html_from_path(path: &std::path::Path) -> N4Result<String> {
+    read_file_to_string(path)
 }
-pub fn read_json_from_path(path: &std::path::Path) -> String {
-    let mut content = String::new();
-    let mut _file = match fs::File::open(&path) {
-        Err(why) => panic!("Couldn't open file: {}", why),
-        Ok(mut _file) => match _file.read_to_string(&mut content) {
-            Err(why) => panic!("Couldn't read file: {}", why),
-            Ok(_) => return content,
-        },
-    };
+
+pub fn read_json_from_path(path: &std::path::Path) -> N4Result<String> {
+    read_file_to_string(path)
 }
 
-pub fn read_css_from_path(path: &std::path::Path) -> String {
-    let mut content = String::new();
-    let mut _file = match fs::File::open(&path) {
-        Err(why) => panic!("Couldn't open file: {}", why),
-        Ok(mut _file) => match _file.read_to_string(&mut content) {
-            Err(why) => panic!("Couldn't read file: {}", why),
-            Ok(_) => return content,
-        },
-    };
+pub fn read_css_from_path(path: &std::path::Path) -> N4Result<String> {
+    read_file_to_string(path)
 }
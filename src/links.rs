@@ -0,0 +1,425 @@
+//! Broken internal link checker
+//!
+//! `Site::check_links` goes over every content item and checks the links in its markdown, its HTML and its
+//! content_list.  A link counts as internal when it has no scheme and isn't protocol relative, absolute ones are
+//! web paths and relative ones are resolved the way a browser would against where export puts the page,
+//! `<location>/index.html` or the directory itself for an `index` page.  The path has to be somewhere export writes
+//! a page, its ContentMeta.path or its web path when that isn't set, or a directory, and a `#fragment` on a page has
+//! to be an id somewhere in that page's rendered output.  A page's web path under a different ContentMeta.path, or a
+//! draft that isn't exported, is a missing page.  content_list entries name content by web path rather than linking
+//! to it, so those only have to be content.  Wiki links are checked on pages that have them turned on, a target no
+//! page matches is a missing page.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use comrak::nodes::NodeValue;
+use comrak::{parse_document, Arena};
+
+use crate::error::N4Result;
+use crate::export::page_output_dir;
+use crate::front_matter::strip_front_matter;
+use crate::web_path::WebPath;
+use crate::wiki::WikiIndex;
+use crate::{read_file_to_string, PageContent, PageSource, Site};
+
+#[derive(Debug, Default)]
+pub struct LinkReport {
+    /// Internal links looked at, external ones aren't counted
+    pub checked: usize,
+    pub broken: Vec<BrokenLink>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// The file the link is written in, the .md, .html or .content_meta
+    pub file: PathBuf,
+    /// 1 based, 0 when the line couldn't be found
+    pub line: usize,
+    /// The link as written
+    pub target: String,
+    pub problem: LinkProblem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkProblem {
+    /// No page is exported at the path and it isn't a directory, or no content at a content_list entry
+    MissingPage,
+    /// The page is there but has no element with the fragment as its id
+    MissingAnchor,
+    /// The path isn't a valid web path, like one that climbs out of the content root
+    InvalidPath,
+}
+
+impl LinkReport {
+    pub fn is_clean(&self) -> bool {
+        self.broken.is_empty()
+    }
+}
+
+impl fmt::Display for LinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Checked {} internal links, {} broken",
+            self.checked,
+            self.broken.len()
+        )?;
+        for link in &self.broken {
+            let problem = match link.problem {
+                LinkProblem::MissingPage => "no such page",
+                LinkProblem::MissingAnchor => "no such anchor",
+                LinkProblem::InvalidPath => "invalid path",
+            };
+            writeln!(
+                f,
+                "  {}:{}: {} ({})",
+                link.file.to_string_lossy(),
+                link.line,
+                link.target,
+                problem
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// A link found in a file, before it's resolved
+struct FoundLink {
+    file: PathBuf,
+    line: usize,
+    target: String,
+    /// What gets checked, the target itself except for wiki links where None means no page matched
+    href: Option<String>,
+    /// The directory a relative target is resolved against, content_list entries are always from the root
+    base: WebPath,
+    /// A content_list entry, a web path to content rather than a link to where it's published
+    listed: bool,
+}
+
+impl Site {
+    /// Checks every internal link in the content tree, see the module docs for what counts as broken
+    pub fn check_links(&self) -> N4Result<LinkReport> {
+        let mut report = LinkReport::default();
+        let mut anchors: HashMap<WebPath, HashSet<String>> = HashMap::new();
+        let pages = self.wiki_pages();
+        let wiki = pages.wiki_index()?;
+        let web_paths = self.content_items(&self.generate_content_state()?)?;
+        let published = self.exported_pages(&web_paths);
+        for web_path in web_paths {
+            let page = match pages.page_parts(&web_path) {
+                Ok(val) => val,
+                Err(why) => {
+                    println!("Link check failure. {} couldn't be read: {}", web_path, why);
+                    continue;
+                }
+            };
+            for found in self.links_in_page(&web_path, &page, &wiki)? {
+                report.checked += 1;
+                let problem = match &found.href {
+                    Some(href) if found.listed => match WebPath::parse(href) {
+                        Ok(val) if self.has_content(&val) => None,
+                        Ok(_) => Some(LinkProblem::MissingPage),
+                        Err(_) => Some(LinkProblem::InvalidPath),
+                    },
+                    Some(href) => {
+                        self.link_problem(&pages, &published, &found.base, href, &mut anchors)
+                    }
+                    None => Some(LinkProblem::MissingPage),
+                };
+                if let Some(problem) = problem {
+                    report.broken.push(BrokenLink {
+                        file: found.file,
+                        line: found.line,
+                        target: found.target,
+                        problem,
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn links_in_page(
+        &self,
+        web_path: &WebPath,
+        page: &PageContent,
+        wiki: &WikiIndex,
+    ) -> N4Result<Vec<FoundLink>> {
+        let mut found: Vec<FoundLink> = Vec::new();
+
+        // MARKDOWN LINKS, line numbers from the AST shifted past any front matter
+        let markdown_path = self.checked_path(web_path, Some("md"))?;
+        if markdown_path.is_file() {
+            let content = read_file_to_string(&markdown_path)?;
+            let body = strip_front_matter(&content);
+            let offset = content[..content.len() - body.len()].matches('\n').count();
+            let arena = Arena::new();
+            let root = parse_document(&arena, body, &page.markdown.options.to_comrak());
+            for node in root.descendants() {
+                let data = node.data.borrow();
+                let (target, href) = match data.value {
+                    NodeValue::Link(ref link) if is_internal(&link.url) => {
+                        (link.url.clone(), Some(link.url.clone()))
+                    }
+                    NodeValue::WikiLink(ref link) => {
+                        (format!("[[{}]]", link.url), wiki.resolve(&link.url))
+                    }
+                    _ => continue,
+                };
+                found.push(FoundLink {
+                    file: markdown_path.clone(),
+                    line: data.sourcepos.start.line + offset,
+                    target,
+                    href,
+                    base: page_output_dir(web_path, &page.meta),
+                    listed: false,
+                });
+            }
+        }
+
+        // HTML LINKS
+        let html_path = self.checked_path(web_path, Some("html"))?;
+        if html_path.is_file() {
+            let content = read_file_to_string(&html_path)?;
+            for (number, line) in content.lines().enumerate() {
+                for target in href_values(line) {
+                    if is_internal(&target) {
+                        found.push(FoundLink {
+                            file: html_path.clone(),
+                            line: number + 1,
+                            href: Some(target.clone()),
+                            target,
+                            base: page_output_dir(web_path, &page.meta),
+                            listed: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        // CONTENT LIST, from the sidecar or the front matter, whichever has it
+        if !page.meta.content_list.is_empty() {
+            let meta_path = self.checked_path(web_path, Some("content_meta"))?;
+            let listed_in = if meta_path.is_file() {
+                meta_path
+            } else {
+                markdown_path
+            };
+            for item in &page.meta.content_list {
+                found.push(FoundLink {
+                    line: line_of(&listed_in, item),
+                    file: listed_in.clone(),
+                    target: item.clone(),
+                    href: Some(item.clone()),
+                    base: WebPath::root(),
+                    listed: true,
+                });
+            }
+        }
+        Ok(found)
+    }
+
+    fn link_problem(
+        &self,
+        pages: &dyn PageSource,
+        published: &HashMap<WebPath, WebPath>,
+        base: &WebPath,
+        target: &str,
+        anchors: &mut HashMap<WebPath, HashSet<String>>,
+    ) -> Option<LinkProblem> {
+        let (path, fragment) = match target.split_once('#') {
+            Some((path, fragment)) => (path, Some(percent_decode(fragment))),
+            None => (target, None),
+        };
+        let path = path.split('?').next().unwrap_or_default();
+        let resolved = if path.is_empty() {
+            base.clone()
+        } else {
+            match resolve(base, &percent_decode(path)) {
+                Some(val) => val,
+                None => return Some(LinkProblem::InvalidPath),
+            }
+        };
+        // The page export writes there, found by its web path for the anchors
+        match published.get(&resolved) {
+            Some(page) => {
+                let fragment = match fragment {
+                    Some(val) if !val.is_empty() => val,
+                    _ => return None,
+                };
+                let ids = anchors
+                    .entry(page.clone())
+                    .or_insert_with(|| page_ids(pages, page));
+                if ids.contains(&fragment) {
+                    None
+                } else {
+                    Some(LinkProblem::MissingAnchor)
+                }
+            }
+            None if self.does_directory_exist(&resolved.to_string()) => None,
+            None => Some(LinkProblem::MissingPage),
+        }
+    }
+}
+
+// Every id in the page's rendered markdown and HTML
+fn page_ids(pages: &dyn PageSource, web_path: &WebPath) -> HashSet<String> {
+    let mut ids: HashSet<String> = HashSet::new();
+    if let Ok(mut page) = pages.page_parts(web_path) {
+        ids.extend(attribute_values(page.markdown.render(), "id"));
+        if let Some(html) = &page.html {
+            ids.extend(attribute_values(&html.body, "id"));
+        }
+    }
+    ids
+}
+
+/// No scheme, not protocol relative and not empty
+fn is_internal(target: &str) -> bool {
+    if target.is_empty() || target.starts_with("//") {
+        return false;
+    }
+    // A scheme is letters, digits, + - . before the first ':' and nothing path like before it
+    match target.find(':') {
+        Some(colon) => {
+            let before = &target[..colon];
+            before.is_empty()
+                || !before
+                    .chars()
+                    .all(|x| x.is_ascii_alphanumeric() || "+-.".contains(x))
+        }
+        None => true,
+    }
+}
+
+/// A link path resolved against the directory it's in, None when it climbs out of the root or has bad segments
+fn resolve(base: &WebPath, path: &str) -> Option<WebPath> {
+    let mut segments: Vec<String> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        base.segments().to_vec()
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment.to_string()),
+        }
+    }
+    WebPath::parse(&segments.join("/")).ok()
+}
+
+fn href_values(line: &str) -> Vec<String> {
+    attribute_values(line, "href")
+}
+
+// Values of name="..." or name='...' attributes, a plain scan that's good enough for hand written HTML
+fn attribute_values(html: &str, name: &str) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    let mut rest = html;
+    let pattern = format!("{}=", name);
+    while let Some(position) = rest.find(&pattern) {
+        let preceded_by_space = rest[..position]
+            .chars()
+            .last()
+            .is_some_and(|x| x.is_whitespace());
+        rest = &rest[position + pattern.len()..];
+        let quote = match rest.chars().next() {
+            Some(val) if val == '"' || val == '\'' => val,
+            _ => continue,
+        };
+        if let Some(end) = rest[1..].find(quote) {
+            if preceded_by_space {
+                values.push(html_unescape(&rest[1..end + 1]));
+            }
+            rest = &rest[end + 1..];
+        }
+    }
+    values
+}
+
+fn html_unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn percent_decode(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = raw
+            .get(index + 1..index + 3)
+            .and_then(|x| u8::from_str_radix(x, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(val)) => {
+                decoded.push(val);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// First line of a file mentioning the text, 0 when it doesn't or can't be read
+fn line_of(path: &Path, text: &str) -> usize {
+    match read_file_to_string(path) {
+        Ok(content) => content
+            .lines()
+            .position(|x| x.contains(text))
+            .map(|x| x + 1)
+            .unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_internal, resolve};
+    use crate::export::page_output_dir;
+    use crate::web_path::WebPath;
+    use crate::ContentMeta;
+
+    #[test]
+    fn internal_links_are_the_ones_without_a_scheme() {
+        assert!(is_internal("/docs/intro"));
+        assert!(is_internal("../intro#setup"));
+        assert!(is_internal("#setup"));
+        assert!(!is_internal("https://example.com/"));
+        assert!(!is_internal("mailto:someone@example.com"));
+        assert!(!is_internal("//cdn.example.com/x.js"));
+    }
+
+    #[test]
+    fn relative_links_resolve_against_the_export_directory() {
+        let meta = ContentMeta {
+            path: String::new(),
+            ..ContentMeta::default()
+        };
+        let from = page_output_dir(&WebPath::parse("/docs/guide/intro").unwrap(), &meta);
+        assert_eq!(
+            resolve(&from, "../setup").unwrap().to_string(),
+            "/docs/guide/setup"
+        );
+        assert_eq!(
+            resolve(&from, "../../api").unwrap().to_string(),
+            "/docs/api"
+        );
+        assert_eq!(resolve(&from, "/about").unwrap().to_string(), "/about");
+        assert!(resolve(&from, "../../../../etc").is_none());
+
+        let index = page_output_dir(&WebPath::parse("/docs/index").unwrap(), &meta);
+        assert_eq!(resolve(&index, "setup").unwrap().to_string(), "/docs/setup");
+    }
+}
@@ -0,0 +1,197 @@
+//! Markdown rendering options
+//!
+//! `SiteConfig.markdown` sets the site wide comrak options.  A section's `MenuItemMeta.markdown` and then a page's
+//! `ContentMeta.markdown` can override any of them, only the fields they set are changed.  Everything defaults to
+//! off, the same as plain CommonMark.
+//!
+//! Pages are read with their markdown source only, rendering happens on the first call that needs the HTML.
+use comrak::options::Plugins;
+use comrak::{
+    format_html_with_plugins, markdown_to_html_with_plugins, parse_document, Arena, Options,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::highlight;
+use crate::toc::render_with_anchors;
+use crate::wiki::{link_wiki_nodes, WikiIndex};
+use crate::{MDContent, PageContent};
+
+/// Renders markdown source to HTML with the given options, wiki links all come out as missing pages
+pub fn render_markdown(source: &str, options: &MarkdownOptions) -> String {
+    render_markdown_with_wiki(source, options, &WikiIndex::default())
+}
+
+/// Renders markdown source to HTML, resolving wiki links through the index when the options turn them on
+pub fn render_markdown_with_wiki(
+    source: &str,
+    options: &MarkdownOptions,
+    wiki: &WikiIndex,
+) -> String {
+    let mut comrak_options = options.to_comrak();
+    let mut plugins = Plugins::default();
+    if options.highlight {
+        plugins.render.codefence_syntax_highlighter = Some(highlight::adapter());
+    }
+    if !options.toc && !options.wiki_links {
+        return markdown_to_html_with_plugins(source, &comrak_options, &plugins);
+    }
+    let arena = Arena::new();
+    let root = parse_document(&arena, source, &comrak_options);
+    if options.wiki_links {
+        link_wiki_nodes(&arena, root, wiki);
+    }
+    if options.toc {
+        // The TOC anchors are the heading ids, comrak's own would be a second id attribute
+        comrak_options.extension.header_id_prefix = None;
+        render_with_anchors(&arena, root, &comrak_options, &plugins)
+    } else {
+        let mut html = String::new();
+        // Writing to a String can't fail
+        format_html_with_plugins(root, &comrak_options, &mut html, &plugins).ok();
+        html
+    }
+}
+
+impl MDContent {
+    /// The HTML, rendered and kept on the first call
+    pub fn render(&mut self) -> &str {
+        if self.rendered.is_none() {
+            self.rendered = Some(self.fresh_render());
+        }
+        self.rendered.as_deref().unwrap_or_default()
+    }
+
+    /// The HTML without keeping it, from the cache when there is one
+    pub fn rendered_html(&self) -> String {
+        match &self.rendered {
+            Some(val) => val.clone(),
+            None => self.fresh_render(),
+        }
+    }
+
+    fn fresh_render(&self) -> String {
+        let html = match &self.wiki {
+            Some(wiki) => render_markdown_with_wiki(&self.source, &self.options, wiki),
+            None => render_markdown(&self.source, &self.options),
+        };
+        match &self.sanitize {
+            Some(sanitize) => sanitize.clean(&html),
+            None => html,
+        }
+    }
+
+    /// Renders the source again with different options, the result is kept
+    pub fn rerender(&mut self, options: &MarkdownOptions) -> &str {
+        self.options = options.clone();
+        self.rendered = None;
+        self.render()
+    }
+}
+
+impl PageContent {
+    /// Renders the markdown of the page and every page in its content list
+    pub fn render_markdown(&mut self) {
+        self.markdown.render();
+        for item in self.list.iter_mut() {
+            item.render_markdown();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MarkdownOptions {
+    pub table: bool,
+    pub strikethrough: bool,
+    pub autolink: bool,
+    pub tasklist: bool,
+    pub footnotes: bool,
+    pub superscript: bool,
+    pub description_lists: bool,
+    /// Adds id attributes to headers, the value is the prefix for them and can be empty
+    pub header_ids: Option<String>,
+    /// Quotes, full stops and hyphens turned into their typographic versions
+    pub smart: bool,
+    /// Soft line breaks rendered as <br />
+    pub hardbreaks: bool,
+    /// Code block languages as `<pre lang="x">` instead of a class on the `<code>`
+    pub github_pre_lang: bool,
+    /// Pass raw HTML and dangerous links through instead of omitting them
+    pub unsafe_html: bool,
+    /// Build PageContent.toc and put its anchors on the rendered headings, replaces header_ids
+    pub toc: bool,
+    /// Syntax highlight fenced code blocks with classes, see Site::highlight_css for the stylesheet
+    pub highlight: bool,
+    /// Resolve `[[Page Title]]` and `[[/section/page]]` links, see the wiki module
+    pub wiki_links: bool,
+}
+
+/// A section or page level change to MarkdownOptions, unset fields keep what's inherited
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MarkdownOverrides {
+    pub table: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub autolink: Option<bool>,
+    pub tasklist: Option<bool>,
+    pub footnotes: Option<bool>,
+    pub superscript: Option<bool>,
+    pub description_lists: Option<bool>,
+    pub header_ids: Option<String>,
+    pub smart: Option<bool>,
+    pub hardbreaks: Option<bool>,
+    pub github_pre_lang: Option<bool>,
+    pub unsafe_html: Option<bool>,
+    pub toc: Option<bool>,
+    pub highlight: Option<bool>,
+    pub wiki_links: Option<bool>,
+}
+
+impl MarkdownOptions {
+    /// These options with the set fields of an override laid over them
+    pub fn with(&self, overrides: &MarkdownOverrides) -> MarkdownOptions {
+        MarkdownOptions {
+            table: overrides.table.unwrap_or(self.table),
+            strikethrough: overrides.strikethrough.unwrap_or(self.strikethrough),
+            autolink: overrides.autolink.unwrap_or(self.autolink),
+            tasklist: overrides.tasklist.unwrap_or(self.tasklist),
+            footnotes: overrides.footnotes.unwrap_or(self.footnotes),
+            superscript: overrides.superscript.unwrap_or(self.superscript),
+            description_lists: overrides
+                .description_lists
+                .unwrap_or(self.description_lists),
+            header_ids: overrides
+                .header_ids
+                .clone()
+                .or_else(|| self.header_ids.clone()),
+            smart: overrides.smart.unwrap_or(self.smart),
+            hardbreaks: overrides.hardbreaks.unwrap_or(self.hardbreaks),
+            github_pre_lang: overrides.github_pre_lang.unwrap_or(self.github_pre_lang),
+            unsafe_html: overrides.unsafe_html.unwrap_or(self.unsafe_html),
+            toc: overrides.toc.unwrap_or(self.toc),
+            highlight: overrides.highlight.unwrap_or(self.highlight),
+            wiki_links: overrides.wiki_links.unwrap_or(self.wiki_links),
+        }
+    }
+
+    pub fn to_comrak(&self) -> Options<'static> {
+        let mut options = Options::default();
+        // EXTENSIONS
+        options.extension.table = self.table;
+        options.extension.strikethrough = self.strikethrough;
+        options.extension.autolink = self.autolink;
+        options.extension.tasklist = self.tasklist;
+        options.extension.footnotes = self.footnotes;
+        options.extension.superscript = self.superscript;
+        options.extension.description_lists = self.description_lists;
+        options.extension.header_id_prefix = self.header_ids.clone();
+        options.extension.wikilinks_title_after_pipe = self.wiki_links;
+        // PARSE
+        options.parse.smart = self.smart;
+        // RENDER
+        options.render.hardbreaks = self.hardbreaks;
+        options.render.github_pre_lang = self.github_pre_lang;
+        options.render.r#unsafe = self.unsafe_html;
+        options
+    }
+}
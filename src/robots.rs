@@ -0,0 +1,114 @@
+//! robots.txt generation
+//!
+//! The groups come from `SiteConfig.robots`.  On top of the configured rules every group gets a Disallow for each
+//! page whose meta sets `noindex`, since a crawler only follows the one group that matches it best.  The Disallow
+//! is the page's location in the sitemap, `ContentMeta.path` when set, percent-encoded the same way and anchored with
+//! `$` so `/docs/` doesn't take the rest of the directory with it and `/secret` leaves `/secret-plans` alone.
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::N4Result;
+use crate::sitemap::{self, url_encode};
+use crate::{content_location, Site};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RobotsConfig {
+    pub groups: Vec<RobotsGroup>,
+    /// Full sitemap URLs, empty means the site's own sitemap.xml under prod_host
+    pub sitemaps: Vec<String>,
+    /// Add a Disallow for each noindex page to every group
+    pub disallow_noindex: bool,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        RobotsConfig {
+            groups: vec![RobotsGroup::default()],
+            sitemaps: Vec::new(),
+            disallow_noindex: true,
+        }
+    }
+}
+
+/// One `User-agent` block, several agents can share the same rules
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RobotsGroup {
+    pub user_agents: Vec<String>,
+    pub allow: Vec<String>,
+    pub disallow: Vec<String>,
+    /// Seconds between requests, not every crawler honors it
+    pub crawl_delay: Option<u32>,
+}
+
+impl Default for RobotsGroup {
+    fn default() -> Self {
+        RobotsGroup {
+            user_agents: vec![String::from("*")],
+            allow: vec![String::from("/")],
+            disallow: Vec::new(),
+            crawl_delay: None,
+        }
+    }
+}
+
+impl Site {
+    /// The robots.txt for the site per SiteConfig.robots
+    pub fn generate_robot_food(&self) -> N4Result<String> {
+        let robots = &self.config().robots;
+        let noindex = if robots.disallow_noindex {
+            self.noindex_paths()?
+        } else {
+            Vec::new()
+        };
+        let mut text = String::new();
+        for group in &robots.groups {
+            for user_agent in &group.user_agents {
+                text.push_str(&format!("User-agent: {}\n", user_agent));
+            }
+            for path in &group.allow {
+                text.push_str(&format!("Allow: {}\n", path));
+            }
+            for path in group.disallow.iter().chain(noindex.iter()) {
+                text.push_str(&format!("Disallow: {}\n", path));
+            }
+            if let Some(delay) = group.crawl_delay {
+                text.push_str(&format!("Crawl-delay: {}\n", delay));
+            }
+            text.push('\n');
+        }
+        if robots.sitemaps.is_empty() {
+            text.push_str(&format!(
+                "Sitemap: {}\n",
+                self.config().absolute_url(&format!(
+                    "/{}",
+                    sitemap::root_file_name(self.config().sitemap_gzip)
+                ))
+            ));
+        } else {
+            for url in &robots.sitemaps {
+                text.push_str(&format!("Sitemap: {}\n", url));
+            }
+        }
+        Ok(text)
+    }
+
+    // Locations of every page marked noindex, a page whose meta can't be read is left alone.  Nothing is written, a
+    // page without meta isn't noindex anyway.
+    fn noindex_paths(&self) -> N4Result<Vec<String>> {
+        let mut paths: Vec<String> = Vec::new();
+        for web_path in self.content_items(&self.generate_content_state()?)? {
+            match self.peek_content_meta(&self.checked_path(&web_path, None)?) {
+                Ok(meta) if meta.noindex => {
+                    paths.push(format!(
+                        "{}$",
+                        url_encode(&content_location(&web_path, &meta))
+                    ));
+                }
+                Ok(_) => {}
+                Err(why) => println!("Robots meta failure. {}", why),
+            }
+        }
+        Ok(paths)
+    }
+}
@@ -0,0 +1,105 @@
+//! HTML sanitization
+//!
+//! Rendered markdown and `.html` bodies go through an allowlist before they reach a page, anything not listed in
+//! `SiteConfig.sanitize` is dropped.  A section whose `.menu_meta` sets `trusted` skips it, for directories where
+//! the HTML is ours and is meant to carry scripts or embeds.  JSON content is data, not markup, and isn't touched.
+use std::collections::{HashMap, HashSet};
+
+use ammonia::Builder;
+use serde_derive::{Deserialize, Serialize};
+
+// script and style are dropped with their contents unless they're allowed outright
+const CONTENT_DROPPING_TAGS: [&str; 2] = ["script", "style"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SanitizeConfig {
+    pub enabled: bool,
+    pub tags: Vec<String>,
+    /// Allowed attributes by tag name, the `*` entry is allowed on every tag
+    pub attributes: HashMap<String, Vec<String>>,
+    /// Schemes allowed in href and src, relative URLs are always allowed
+    pub url_schemes: Vec<String>,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        #[rustfmt::skip]
+        let tags = [
+            "a", "abbr", "article", "aside", "b", "blockquote", "br", "caption", "cite", "code", "col",
+            "colgroup", "dd", "del", "details", "div", "dl", "dt", "em", "figcaption", "figure", "footer",
+            "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "i", "img", "input", "ins", "kbd", "li",
+            "mark", "nav", "ol", "p", "pre", "q", "s", "samp", "section", "small", "span", "strong", "sub",
+            "summary", "sup", "table", "tbody", "td", "tfoot", "th", "thead", "time", "tr", "u", "ul", "var",
+        ];
+        let attributes = [
+            ("*", vec!["class", "id", "lang", "title"]),
+            ("a", vec!["href", "hreflang"]),
+            ("img", vec!["src", "alt", "width", "height"]),
+            // Task list checkboxes
+            ("input", vec!["type", "checked", "disabled"]),
+            ("ol", vec!["start"]),
+            ("td", vec!["align", "colspan", "rowspan"]),
+            ("th", vec!["align", "colspan", "rowspan"]),
+            ("time", vec!["datetime"]),
+        ];
+        SanitizeConfig {
+            enabled: true,
+            tags: tags.iter().map(|x| x.to_string()).collect(),
+            attributes: attributes
+                .iter()
+                .map(|(tag, names)| {
+                    (
+                        tag.to_string(),
+                        names.iter().map(|x| x.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            url_schemes: vec![
+                String::from("http"),
+                String::from("https"),
+                String::from("mailto"),
+            ],
+        }
+    }
+}
+
+impl SanitizeConfig {
+    /// The HTML with everything outside of the allowlist removed
+    pub fn clean(&self, html: &str) -> String {
+        let tags: HashSet<&str> = self.tags.iter().map(|x| x.as_str()).collect();
+        let mut generic_attributes: HashSet<&str> = HashSet::new();
+        let mut tag_attributes: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for (tag, names) in &self.attributes {
+            let names = names.iter().map(|x| x.as_str());
+            if tag == "*" {
+                generic_attributes.extend(names);
+            } else {
+                tag_attributes
+                    .entry(tag.as_str())
+                    .or_default()
+                    .extend(names);
+            }
+        }
+        let dropping: HashSet<&str> = CONTENT_DROPPING_TAGS
+            .iter()
+            .cloned()
+            .filter(|x| !tags.contains(x) && !tag_attributes.contains_key(x))
+            .collect();
+        // ammonia adds its own rel to links and refuses to run when rel is also allowed from the content
+        let rel_allowed = generic_attributes.contains("rel")
+            || tag_attributes.get("a").is_some_and(|x| x.contains("rel"));
+
+        let mut builder = Builder::default();
+        builder
+            .tags(tags)
+            .clean_content_tags(dropping)
+            .generic_attributes(generic_attributes)
+            .tag_attributes(tag_attributes)
+            .url_schemes(self.url_schemes.iter().map(|x| x.as_str()).collect());
+        if rel_allowed {
+            builder.link_rel(None);
+        }
+        builder.clean(html).to_string()
+    }
+}
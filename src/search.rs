@@ -0,0 +1,391 @@
+//! Full text search
+//!
+//! `Site::search_index` builds an inverted index over every content item: the words of its rendered markdown, its
+//! HTML body as text, and its `ContentMeta` title and description.  Title words count for more than description
+//! words and those for more than body words.  Queries are ranked with BM25 and each hit comes with a snippet of the
+//! page text around the first match.  Hidden paths, noindex pages and pages that aren't listed are left out, the
+//! same as the sitemap.
+//!
+//! The index saves to and loads from a JSON file, and `SearchIndex::to_compact_json` gives the smaller form a static
+//! export ships for searching in the browser:
+//!
+//! ```text
+//! {"documents": [[location, title, description, length], ...],
+//!  "terms": {"word": [document, weight, document, weight, ...], ...}}
+//! ```
+//!
+//! where document is a position in documents and weight the field weighted count of the word in it.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::content_index::ContentIndex;
+use crate::error::{N4Error, N4Result};
+use crate::web_path::WebPath;
+use crate::{content_location, PageContent, PageSource, Site};
+
+const TITLE_WEIGHT: u32 = 5;
+const DESCRIPTION_WEIGHT: u32 = 2;
+const BODY_WEIGHT: u32 = 1;
+// BM25 term frequency saturation and length normalization
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+const SNIPPET_WORDS: usize = 24;
+
+// Tags that end a block of text, their contents shouldn't run into the next block's
+#[rustfmt::skip]
+const BLOCK_TAGS: [&str; 28] = [
+    "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption", "figure", "footer",
+    "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "nav", "ol", "p", "pre", "section", "td", "ul",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Write the compact index into static exports
+    pub export: bool,
+    /// Its file name in the export directory
+    pub export_file: String,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            export: true,
+            export_file: String::from("search_index.json"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SearchIndex {
+    documents: Vec<SearchDocument>,
+    terms: BTreeMap<String, Vec<Posting>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct SearchDocument {
+    location: String,
+    title: String,
+    description: String,
+    /// The page as plain text, for snippets
+    text: String,
+    /// Field weighted word count
+    length: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Posting {
+    document: u32,
+    weight: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// The page's ContentMeta.path, or its web path when that isn't set
+    pub location: String,
+    pub title: String,
+    pub description: String,
+    pub score: f32,
+    /// Plain text around the first match, … where it was cut
+    pub snippet: String,
+}
+
+impl SearchIndex {
+    /// Adds a page under its location
+    pub fn add_page(&mut self, web_path: &WebPath, page: &PageContent) {
+        let location = content_location(web_path, &page.meta);
+        let mut text = html_to_text(&page.markdown.rendered_html());
+        if let Some(html) = &page.html {
+            text.push(' ');
+            text.push_str(&html_to_text(&html.body));
+        }
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+        let mut weights: HashMap<String, u32> = HashMap::new();
+        for (field, weight) in [
+            (page.meta.title.as_str(), TITLE_WEIGHT),
+            (page.meta.description.as_str(), DESCRIPTION_WEIGHT),
+            (text.as_str(), BODY_WEIGHT),
+        ] {
+            for term in tokenize(field) {
+                *weights.entry(term).or_default() += weight;
+            }
+        }
+        let document = self.documents.len() as u32;
+        for (term, weight) in weights.iter() {
+            self.terms.entry(term.clone()).or_default().push(Posting {
+                document,
+                weight: *weight,
+            });
+        }
+        self.documents.push(SearchDocument {
+            location,
+            title: page.meta.title.clone(),
+            description: page.meta.description.clone(),
+            text,
+            length: weights.values().sum(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// The best matches for a query, any of its words can match and pages matching more of them rank higher
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query_terms: HashSet<String> = tokenize(query).collect();
+        if query_terms.is_empty() || self.documents.is_empty() {
+            return Vec::new();
+        }
+        let count = self.documents.len() as f32;
+        let average_length = self.documents.iter().map(|x| x.length as f32).sum::<f32>() / count;
+
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for term in &query_terms {
+            let postings = match self.terms.get(term) {
+                Some(val) => val,
+                None => continue,
+            };
+            let matching = postings.len() as f32;
+            let idf = (1.0 + (count - matching + 0.5) / (matching + 0.5)).ln();
+            for posting in postings {
+                let length = self.documents[posting.document as usize].length as f32;
+                let weight = posting.weight as f32;
+                let norm = K1 * (1.0 - B + B * length / average_length.max(1.0));
+                *scores.entry(posting.document).or_default() +=
+                    idf * weight * (K1 + 1.0) / (weight + norm);
+            }
+        }
+
+        let mut ranked: Vec<(u32, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| {
+            b.1.total_cmp(&a.1).then_with(|| {
+                let a = &self.documents[a.0 as usize].location;
+                a.cmp(&self.documents[b.0 as usize].location)
+            })
+        });
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(document, score)| {
+                let document = &self.documents[document as usize];
+                SearchHit {
+                    location: document.location.clone(),
+                    title: document.title.clone(),
+                    description: document.description.clone(),
+                    score,
+                    snippet: snippet(document, &query_terms),
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the full index, snippets and all, as JSON
+    pub fn save(&self, path: &Path) -> N4Result<()> {
+        let contents = serde_json::to_string(self).map_err(N4Error::Serialize)?;
+        fs::write(path, contents).map_err(|why| N4Error::io(path, why))
+    }
+
+    /// Reads an index written by save
+    pub fn load(path: &Path) -> N4Result<SearchIndex> {
+        let contents = fs::read_to_string(path).map_err(|why| N4Error::io(path, why))?;
+        serde_json::from_str(&contents).map_err(|source| N4Error::SearchIndexParse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// The index without page text for client side search, see the module docs for the layout
+    pub fn to_compact_json(&self) -> N4Result<String> {
+        let documents: Vec<_> = self
+            .documents
+            .iter()
+            .map(|x| json!([x.location, x.title, x.description, x.length]))
+            .collect();
+        let terms: BTreeMap<&String, Vec<u32>> = self
+            .terms
+            .iter()
+            .map(|(term, postings)| {
+                let flat = postings
+                    .iter()
+                    .flat_map(|x| [x.document, x.weight])
+                    .collect();
+                (term, flat)
+            })
+            .collect();
+        serde_json::to_string(&json!({ "documents": documents, "terms": terms }))
+            .map_err(N4Error::Serialize)
+    }
+}
+
+impl Site {
+    /// The search index over every content item, a page that can't be read is left out
+    pub fn search_index(&self) -> N4Result<SearchIndex> {
+        let web_paths = self.content_items(&self.generate_content_state()?)?;
+        Ok(index_pages(&self.wiki_pages(), &web_paths))
+    }
+}
+
+impl ContentIndex {
+    /// The search index over the cached pages
+    pub fn search_index(&self) -> SearchIndex {
+        let mut web_paths: Vec<WebPath> = self.pages().map(|(x, _)| x.clone()).collect();
+        web_paths.sort();
+        index_pages(self, &web_paths)
+    }
+}
+
+fn index_pages(source: &dyn PageSource, web_paths: &[WebPath]) -> SearchIndex {
+    let mut index = SearchIndex::default();
+    for web_path in web_paths {
+        if web_path.is_hidden() {
+            continue;
+        }
+        match source.page_parts(web_path) {
+            Ok(page) if !page.meta.noindex && source.site_config().is_listed(&page.meta) => {
+                index.add_page(web_path, &page)
+            }
+            Ok(_) => {}
+            Err(why) => println!("Search index failure. {}", why),
+        }
+    }
+    index
+}
+
+/// Lowercased runs of letters and digits, single characters aren't worth indexing
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|x: char| !x.is_alphanumeric())
+        .filter(|x| x.chars().nth(1).is_some())
+        .map(|x| x.to_lowercase())
+}
+
+fn snippet(document: &SearchDocument, query_terms: &HashSet<String>) -> String {
+    let words: Vec<&str> = document.text.split(' ').filter(|x| !x.is_empty()).collect();
+    let first_match = words
+        .iter()
+        .position(|word| tokenize(word).any(|x| query_terms.contains(&x)));
+    let start = match first_match {
+        Some(val) => val.saturating_sub(SNIPPET_WORDS / 4),
+        // Only the title or description matched
+        None if !document.description.is_empty() => return document.description.clone(),
+        None => 0,
+    };
+    let end = (start + SNIPPET_WORDS).min(words.len());
+    let mut snippet = words[start..end].join(" ");
+    if start > 0 {
+        snippet.insert_str(0, "… ");
+    }
+    if end < words.len() {
+        snippet.push_str(" …");
+    }
+    snippet
+}
+
+/// The text of an HTML fragment, tags dropped along with script and style contents
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..open]));
+        let close = match rest[open..].find('>') {
+            Some(val) => open + val,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        let tag = &rest[open + 1..close];
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|x| x.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        rest = &rest[close + 1..];
+        if (name == "script" || name == "style") && !tag.starts_with('/') {
+            let end_tag = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&end_tag) {
+                Some(val) => &rest[val..],
+                None => "",
+            };
+        }
+        if BLOCK_TAGS.contains(&name.as_str()) {
+            text.push(' ');
+        }
+    }
+    text.push_str(&decode_entities(rest));
+    text
+}
+
+fn decode_entities(raw: &str) -> String {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(val) if val <= 10 => val,
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let character = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            entity => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|x| u32::from_str_radix(x, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|x| x.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(val) => {
+                decoded.push(val);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{html_to_text, tokenize};
+
+    #[test]
+    fn html_becomes_plain_words() {
+        let text = html_to_text(
+            "<h1>Fish &amp; Chips</h1><p>Hot<em>ly</em> served</p><script>x()</script>&#33;",
+        );
+        assert_eq!(
+            text.split_whitespace().collect::<Vec<&str>>(),
+            ["Fish", "&", "Chips", "Hotly", "served", "!"]
+        );
+    }
+
+    #[test]
+    fn tokens_are_lowercase_words() {
+        let tokens: Vec<String> = tokenize("N4's Search-Index, a v2 test").collect();
+        assert_eq!(tokens, ["n4", "search", "index", "v2", "test"]);
+    }
+}
@@ -0,0 +1,298 @@
+//! sitemap.xml serialization for the entries from Site::generate_sitemap
+//!
+//! Follows the sitemaps.org protocol: one urlset file holds at most 50,000 URLs and 50MB uncompressed, past that
+//! the entries are split over `sitemap-1.xml`, `sitemap-2.xml`, ... and `sitemap.xml` becomes a sitemap index
+//! pointing at them.  With gzip on every file is compressed and gets a `.gz` suffix.  Site::generate_sitemap
+//! percent-encodes each path segment of its locations, the writers only encode what RFC 3986 never allows, spaces and
+//! non-ASCII, before the XML escaping.
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::{N4Error, N4Result};
+use crate::{Site, SiteMapEntry};
+
+pub const MAX_URLS_PER_SITEMAP: usize = 50_000;
+pub const MAX_BYTES_PER_SITEMAP: usize = 50 * 1024 * 1024;
+
+const URLSET_OPEN: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"
+);
+const URLSET_CLOSE: &str = "</urlset>\n";
+const INDEX_OPEN: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"
+);
+const INDEX_CLOSE: &str = "</sitemapindex>\n";
+
+/// The protocol's changefreq values
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeFreq {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl ChangeFreq {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeFreq::Always => "always",
+            ChangeFreq::Hourly => "hourly",
+            ChangeFreq::Daily => "daily",
+            ChangeFreq::Weekly => "weekly",
+            ChangeFreq::Monthly => "monthly",
+            ChangeFreq::Yearly => "yearly",
+            ChangeFreq::Never => "never",
+        }
+    }
+}
+
+/// One file of sitemap output, name is relative to the site root
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapFile {
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Limits and output format for render_sitemaps, the defaults are the protocol maximums without gzip
+#[derive(Debug, Clone)]
+pub struct SitemapOptions {
+    pub max_urls: usize,
+    pub max_bytes: usize,
+    pub gzip: bool,
+}
+
+impl Default for SitemapOptions {
+    fn default() -> Self {
+        SitemapOptions {
+            max_urls: MAX_URLS_PER_SITEMAP,
+            max_bytes: MAX_BYTES_PER_SITEMAP,
+            gzip: false,
+        }
+    }
+}
+
+/// Name of the file crawlers should be pointed at, the single urlset or the index
+pub fn root_file_name(gzip: bool) -> &'static str {
+    if gzip {
+        "sitemap.xml.gz"
+    } else {
+        "sitemap.xml"
+    }
+}
+
+/// A single sitemaps.org urlset document, no limits applied
+pub fn render_sitemap_xml(entries: &[SiteMapEntry]) -> String {
+    let mut xml = String::from(URLSET_OPEN);
+    for entry in entries {
+        xml.push_str(&url_element(entry));
+    }
+    xml.push_str(URLSET_CLOSE);
+    xml
+}
+
+/// A sitemap index document pointing at the given sitemap URLs with their lastmod
+pub fn render_sitemap_index(sitemaps: &[(String, DateTime<Utc>)]) -> String {
+    let mut xml = String::from(INDEX_OPEN);
+    for (location, lastmod) in sitemaps {
+        xml.push_str(&format!(
+            "  <sitemap>\n    <loc>{}</loc>\n    <lastmod>{}</lastmod>\n  </sitemap>\n",
+            xml_escape(&encode_unsafe_bytes(location)),
+            w3c_datetime(lastmod)
+        ));
+    }
+    xml.push_str(INDEX_CLOSE);
+    xml
+}
+
+/// All the sitemap files for a set of entries.  Entries that fit the limits give a single `sitemap.xml`, otherwise
+/// numbered urlset parts plus a `sitemap.xml` index whose locations are under base_url.
+pub fn render_sitemaps(
+    entries: &[SiteMapEntry],
+    base_url: &str,
+    options: &SitemapOptions,
+) -> N4Result<Vec<SitemapFile>> {
+    let mut parts: Vec<(String, DateTime<Utc>)> = Vec::new();
+    let mut current = String::from(URLSET_OPEN);
+    let mut current_urls = 0;
+    let mut current_lastmod = DateTime::<Utc>::default();
+    let fixed_size = URLSET_OPEN.len() + URLSET_CLOSE.len();
+
+    for entry in entries {
+        let element = url_element(entry);
+        let full = current_urls >= options.max_urls
+            || current.len() + element.len() + URLSET_CLOSE.len() > options.max_bytes;
+        if current_urls > 0 && full {
+            current.push_str(URLSET_CLOSE);
+            parts.push((current, current_lastmod));
+            current = String::from(URLSET_OPEN);
+            current_urls = 0;
+            current_lastmod = DateTime::<Utc>::default();
+        }
+        if element.len() + fixed_size > options.max_bytes {
+            return Err(N4Error::InvalidConfig(format!(
+                "Sitemap entry is larger than the {} byte limit: {}",
+                options.max_bytes, entry.location
+            )));
+        }
+        current.push_str(&element);
+        current_urls += 1;
+        current_lastmod = current_lastmod.max(entry.lastmod);
+    }
+    current.push_str(URLSET_CLOSE);
+    parts.push((current, current_lastmod));
+
+    let extension = if options.gzip { ".gz" } else { "" };
+    if parts.len() == 1 {
+        return Ok(vec![SitemapFile {
+            name: root_file_name(options.gzip).to_string(),
+            contents: encode(parts.remove(0).0, options.gzip)?,
+        }]);
+    }
+
+    let mut files: Vec<SitemapFile> = Vec::new();
+    let mut index_entries: Vec<(String, DateTime<Utc>)> = Vec::new();
+    for (number, (xml, lastmod)) in parts.into_iter().enumerate() {
+        let name = format!("sitemap-{}.xml{}", number + 1, extension);
+        index_entries.push((
+            format!("{}/{}", base_url.trim_end_matches('/'), name),
+            lastmod,
+        ));
+        files.push(SitemapFile {
+            name,
+            contents: encode(xml, options.gzip)?,
+        });
+    }
+    files.push(SitemapFile {
+        name: root_file_name(options.gzip).to_string(),
+        contents: encode(render_sitemap_index(&index_entries), options.gzip)?,
+    });
+    Ok(files)
+}
+
+impl Site {
+    /// The site's sitemap files, split and compressed per the config
+    pub fn generate_sitemap_files(&self) -> N4Result<Vec<SitemapFile>> {
+        render_sitemaps(
+            &self.generate_sitemap()?,
+            &self.config().prod_host,
+            &self.sitemap_options(),
+        )
+    }
+
+    /// Protocol limits with the configured compression
+    pub fn sitemap_options(&self) -> SitemapOptions {
+        SitemapOptions {
+            gzip: self.config().sitemap_gzip,
+            ..SitemapOptions::default()
+        }
+    }
+}
+
+/// Deserializer for the optional sitemap_priority meta fields, the protocol only allows 0.0 to 1.0
+pub(crate) fn deserialize_priority<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let priority = Option::<f32>::deserialize(deserializer)?;
+    match priority {
+        Some(val) if !(0.0..=1.0).contains(&val) => Err(serde::de::Error::custom(format!(
+            "sitemap_priority must be between 0.0 and 1.0, got {}",
+            val
+        ))),
+        _ => Ok(priority),
+    }
+}
+
+fn url_element(entry: &SiteMapEntry) -> String {
+    let changefreq = match entry.changefreq {
+        Some(val) => format!("    <changefreq>{}</changefreq>\n", val.as_str()),
+        None => String::new(),
+    };
+    format!(
+        "  <url>\n    <loc>{}</loc>\n    <lastmod>{}</lastmod>\n{}    <priority>{}</priority>\n  </url>\n",
+        xml_escape(&encode_unsafe_bytes(&entry.location)),
+        w3c_datetime(&entry.lastmod),
+        changefreq,
+        xml_escape(&entry.priority)
+    )
+}
+
+fn w3c_datetime(datetime: &DateTime<Utc>) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%S+00:00").to_string()
+}
+
+fn encode(xml: String, gzip: bool) -> N4Result<Vec<u8>> {
+    if !gzip {
+        return Ok(xml.into_bytes());
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(xml.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map_err(|why| N4Error::io(std::path::Path::new("sitemap.xml.gz"), why))
+}
+
+/// Percent-encodes each segment of a site path, everything outside RFC 3986's unreserved characters is escaped so a
+/// `#`, `?` or `%` in a file name stays part of the path
+pub(crate) fn url_encode(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            let mut encoded = String::with_capacity(segment.len());
+            for byte in segment.bytes() {
+                match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        encoded.push(char::from(byte))
+                    }
+                    _ => encoded.push_str(&format!("%{:02X}", byte)),
+                }
+            }
+            encoded
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Percent-encodes the bytes RFC 3986 doesn't allow anywhere in a URL, reserved characters and existing escapes stay
+/// as they are so a URL that's already encoded, like the ones Site::generate_sitemap gives, comes through unchanged
+fn encode_unsafe_bytes(raw: &str) -> String {
+    let mut encoded = String::with_capacity(raw.len());
+    for byte in raw.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(char::from(byte)),
+            b'-' | b'.' | b'_' | b'~' | b'%' => encoded.push(char::from(byte)),
+            b':' | b'/' | b'?' | b'#' | b'[' | b']' | b'@' => encoded.push(char::from(byte)),
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Escapes the five XML special characters
+pub(crate) fn xml_escape(raw: &str) -> String {
+    let mut escaped = String::with_capacity(raw.len());
+    for character in raw.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
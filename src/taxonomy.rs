@@ -0,0 +1,262 @@
+//! Tags and categories
+//!
+//! Pages list their terms in `ContentMeta.tags` and `ContentMeta.categories` and `Site::taxonomy` collects them over
+//! the content tree, so tag pages and tag clouds come from the pages themselves instead of a hand kept
+//! content_list.  Terms are matched by their trimmed, lowercased name, `Rust`, `rust` and ` RUST ` are the same tag,
+//! and keep the spelling of the first page that uses them.  The slug is only for the term's URL, `C++`, `C#` and `C`
+//! are three tags and when their slugs collide the later ones get a number, `c`, `c-2` and `c-3` in the order pages
+//! first use them.  Hidden paths and pages that aren't listed are left out like they are from the sitemap.
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::content_index::ContentIndex;
+use crate::dates::page_date;
+use crate::error::N4Result;
+use crate::toc::slugify;
+use crate::web_path::WebPath;
+use crate::{assemble_page, PageContent, PageSource, Site};
+
+// Size steps in a tag cloud, 1 for the least used terms to this for the most used
+const CLOUD_LEVELS: u32 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaxonomyKind {
+    Tags,
+    Categories,
+}
+
+/// Listing order for a term's pages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TermSort {
+    /// Lowest ContentMeta.weight first, like read_full_dir_sorted
+    Weight,
+    /// Newest first
+    Date,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Taxonomy {
+    /// Keyed by the trimmed, lowercased name
+    pub tags: BTreeMap<String, TaxonomyTerm>,
+    pub categories: BTreeMap<String, TaxonomyTerm>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaxonomyTerm {
+    pub name: String,
+    pub slug: String,
+    /// In web path order
+    pub pages: Vec<TermPage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TermPage {
+    pub web_path: WebPath,
+    pub weight: u32,
+    /// ContentMeta.published, or the newest modified time of the page's files
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CloudEntry {
+    pub name: String,
+    pub slug: String,
+    pub count: usize,
+    /// 1 to 5 on a log scale of count, for picking a font size
+    pub level: u32,
+}
+
+impl Taxonomy {
+    /// Adds a page under each of its tags and categories
+    pub fn add_page(&mut self, web_path: &WebPath, page: &PageContent) {
+        let entry = TermPage {
+            web_path: web_path.clone(),
+            weight: page.meta.weight,
+            date: page_date(page),
+        };
+        for (terms, names) in [
+            (&mut self.tags, &page.meta.tags),
+            (&mut self.categories, &page.meta.categories),
+        ] {
+            for name in names {
+                let name = name.trim();
+                if name.is_empty() {
+                    continue;
+                }
+                let key = term_key(name);
+                // Only a new term takes a slug, an existing one keeps the one it has
+                let slug = match terms.get(&key) {
+                    Some(_) => String::new(),
+                    None => unique_slug(terms, name),
+                };
+                let term = terms.entry(key).or_insert_with(|| TaxonomyTerm {
+                    name: name.to_string(),
+                    slug,
+                    pages: Vec::new(),
+                });
+                if !term.pages.iter().any(|x| x.web_path == *web_path) {
+                    term.pages.push(entry.clone());
+                }
+            }
+        }
+    }
+
+    pub fn terms(&self, kind: TaxonomyKind) -> &BTreeMap<String, TaxonomyTerm> {
+        match kind {
+            TaxonomyKind::Tags => &self.tags,
+            TaxonomyKind::Categories => &self.categories,
+        }
+    }
+
+    /// A term by its name, or by its slug for looking one up from its URL
+    pub fn term(&self, kind: TaxonomyKind, name: &str) -> Option<&TaxonomyTerm> {
+        let terms = self.terms(kind);
+        terms
+            .get(&term_key(name))
+            .or_else(|| terms.values().find(|x| x.slug == name.trim()))
+    }
+
+    /// The web paths for a term in listing order, empty for a term no page uses
+    pub fn sorted_pages(&self, kind: TaxonomyKind, name: &str, sort: TermSort) -> Vec<WebPath> {
+        let mut pages: Vec<&TermPage> = match self.term(kind, name) {
+            Some(term) => term.pages.iter().collect(),
+            None => return Vec::new(),
+        };
+        match sort {
+            TermSort::Weight => pages.sort_by(|a, b| {
+                a.weight
+                    .cmp(&b.weight)
+                    .then_with(|| a.web_path.cmp(&b.web_path))
+            }),
+            TermSort::Date => pages.sort_by(|a, b| {
+                b.date
+                    .cmp(&a.date)
+                    .then_with(|| a.web_path.cmp(&b.web_path))
+            }),
+        }
+        pages.into_iter().map(|x| x.web_path.clone()).collect()
+    }
+
+    /// Every term with its page count and size level, in slug order
+    pub fn cloud(&self, kind: TaxonomyKind) -> Vec<CloudEntry> {
+        let terms = self.terms(kind);
+        let counts = terms.values().map(|x| x.pages.len());
+        let (least, most) = match (counts.clone().min(), counts.max()) {
+            (Some(least), Some(most)) => ((least as f64).ln(), (most as f64).ln()),
+            _ => return Vec::new(),
+        };
+        let mut entries: Vec<CloudEntry> = terms
+            .values()
+            .map(|term| {
+                let count = term.pages.len();
+                let level = if most > least {
+                    let scaled = ((count as f64).ln() - least) / (most - least);
+                    1 + (scaled * f64::from(CLOUD_LEVELS - 1)).round() as u32
+                } else {
+                    1
+                };
+                CloudEntry {
+                    name: term.name.clone(),
+                    slug: term.slug.clone(),
+                    count,
+                    level,
+                }
+            })
+            .collect();
+        entries.sort_by(|a, b| a.slug.cmp(&b.slug));
+        entries
+    }
+}
+
+impl Site {
+    /// Tags and categories over every content item
+    pub fn taxonomy(&self) -> N4Result<Taxonomy> {
+        let web_paths = self.content_items(&self.generate_content_state()?)?;
+        Ok(build_taxonomy(&self.wiki_pages(), &web_paths))
+    }
+
+    /// The pages with a tag or category, content_list pages included, in the given order
+    pub fn term_pages(
+        &self,
+        kind: TaxonomyKind,
+        name: &str,
+        sort: TermSort,
+    ) -> N4Result<Vec<PageContent>> {
+        term_pages(&self.wiki_pages(), &self.taxonomy()?, kind, name, sort)
+    }
+}
+
+impl ContentIndex {
+    /// Tags and categories over the cached pages
+    pub fn taxonomy(&self) -> Taxonomy {
+        let mut web_paths: Vec<WebPath> = self.pages().map(|(x, _)| x.clone()).collect();
+        web_paths.sort();
+        build_taxonomy(self, &web_paths)
+    }
+
+    /// Same as Site::term_pages from the cache
+    pub fn term_pages(
+        &self,
+        kind: TaxonomyKind,
+        name: &str,
+        sort: TermSort,
+    ) -> N4Result<Vec<PageContent>> {
+        term_pages(self, &self.taxonomy(), kind, name, sort)
+    }
+}
+
+fn term_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+// The name's slug, numbered from 2 when another term already has it.  A name with nothing to slug, all punctuation
+// say, starts from `term`.
+fn unique_slug(terms: &BTreeMap<String, TaxonomyTerm>, name: &str) -> String {
+    let base = match slugify(name) {
+        val if val.is_empty() => String::from("term"),
+        val => val,
+    };
+    let taken = |slug: &str| terms.values().any(|x| x.slug == slug);
+    let mut slug = base.clone();
+    let mut number = 2;
+    while taken(&slug) {
+        slug = format!("{}-{}", base, number);
+        number += 1;
+    }
+    slug
+}
+
+fn build_taxonomy(source: &dyn PageSource, web_paths: &[WebPath]) -> Taxonomy {
+    let mut taxonomy = Taxonomy::default();
+    for web_path in web_paths {
+        if web_path.is_hidden() {
+            continue;
+        }
+        match source.page_parts(web_path) {
+            Ok(page) if source.site_config().is_listed(&page.meta) => {
+                taxonomy.add_page(web_path, &page)
+            }
+            Ok(_) => {}
+            Err(why) => println!("Taxonomy failure. {}", why),
+        }
+    }
+    taxonomy
+}
+
+fn term_pages(
+    source: &dyn PageSource,
+    taxonomy: &Taxonomy,
+    kind: TaxonomyKind,
+    name: &str,
+    sort: TermSort,
+) -> N4Result<Vec<PageContent>> {
+    taxonomy
+        .sorted_pages(kind, name, sort)
+        .into_iter()
+        .map(|web_path| assemble_page(source, web_path, &mut Vec::new()))
+        .collect()
+}
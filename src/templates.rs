@@ -0,0 +1,248 @@
+//! Page templating
+//!
+//! Templates are Tera `*.html` files from `SiteConfig.template_dir`, loaded once per Site.  The template for a page is
+//! the first one that exists out of the page's `ContentMeta.template_override`, for a section's `index` page the
+//! section's `MenuItemMeta.template_override`, its section's `MenuItemMeta.section_template` and `default`.  A built
+//! in `default.html` is always there as the last resort and a template dir can replace it.
+//!
+//! Every template gets the same context:
+//!     page: PageContent
+//!     menus: the site menus from Site::tree_to_menus
+//!     includes: PageIncludes, the merged section and page classes, JS and CSS
+//!     site: { prod_host }
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_derive::Serialize;
+use tera::{Context, Tera};
+
+use crate::error::{N4Error, N4Result};
+use crate::sitemap::xml_escape;
+use crate::{MenuItem, MenuItemMeta, PageContent, Site};
+
+pub const DEFAULT_TEMPLATE: &str = "default.html";
+
+const DEFAULT_TEMPLATE_SOURCE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{ page.meta.title }}</title>
+<meta name="description" content="{{ page.meta.description }}">
+{% for href in includes.css %}<link rel="stylesheet" href="{{ href }}">
+{% endfor %}{% if includes.css_inline %}<style>{{ includes.css_inline | safe }}</style>
+{% endif %}</head>
+<body class="{{ includes.body_class }}">
+{% if page.html %}{{ page.html.body | safe }}{% else %}{{ page.markdown.rendered | safe }}{% endif %}
+{% for src in includes.javascript %}<script src="{{ src }}"></script>
+{% endfor %}{% if includes.javascript_inline %}<script>{{ includes.javascript_inline | safe }}</script>
+{% endif %}</body>
+</html>
+"#;
+
+/// The CSS and JS a page pulls in, section level values first then the page's own
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PageIncludes {
+    pub body_class: String,
+    pub javascript: Vec<String>,
+    pub javascript_inline: String,
+    pub css: Vec<String>,
+    pub css_inline: String,
+}
+
+impl PageIncludes {
+    /// `sections` are the page's section metas from its own directory up, nearest first, like
+    /// Site::ancestor_section_metas gives.  The section class is the nearest one's and the section JS and CSS are
+    /// inherited from every level, outermost first.  An `index` page is its directory's page, so the section's
+    /// content_class and its own JS and CSS go on it ahead of the page's.
+    pub fn for_page(page: &PageContent, sections: &[MenuItemMeta]) -> Self {
+        let section = &page.section_meta;
+        let meta = &page.meta;
+        let mut classes: Vec<&str> =
+            vec![sections.first().unwrap_or(section).section_class.as_str()];
+        let mut javascript: Vec<String> = Vec::new();
+        let mut css: Vec<String> = Vec::new();
+        for ancestor in sections.iter().rev() {
+            javascript = merged(&javascript, &ancestor.section_javascript_include);
+            css = merged(&css, &ancestor.section_css_include);
+        }
+        let mut javascript_inline: Vec<&str> = Vec::new();
+        let mut css_inline: Vec<&str> = Vec::new();
+        if page.web_path.file_name() == Some("index") {
+            classes.push(&section.content_class);
+            javascript = merged(&javascript, &section.javascript_include);
+            css = merged(&css, &section.css_include);
+            javascript_inline.push(&section.javascript_inline);
+            css_inline.push(&section.css_inline);
+        }
+        classes.push(&meta.content_class);
+        javascript_inline.push(&meta.javascript_inline);
+        css_inline.push(&meta.css_inline);
+        PageIncludes {
+            body_class: joined(&classes, " "),
+            javascript: merged(&javascript, &meta.javascript_include),
+            javascript_inline: joined(&javascript_inline, "\n"),
+            css: merged(&css, &meta.css_include),
+            css_inline: joined(&css_inline, "\n"),
+        }
+    }
+}
+
+fn joined(parts: &[&str], separator: &str) -> String {
+    parts
+        .iter()
+        .filter(|x| !x.is_empty())
+        .cloned()
+        .collect::<Vec<&str>>()
+        .join(separator)
+}
+
+// Section entries first, duplicates from the page dropped
+fn merged(section: &[String], page: &[String]) -> Vec<String> {
+    let mut all: Vec<String> = section.to_vec();
+    for item in page {
+        if !all.contains(item) {
+            all.push(item.clone());
+        }
+    }
+    all
+}
+
+#[derive(Debug, Clone)]
+pub struct Templates {
+    tera: Tera,
+}
+
+impl Templates {
+    /// Only the built in default template
+    pub fn builtin() -> Self {
+        let mut tera = Tera::default();
+        // Tera's own escaper also does '/', which mangles every URL in an attribute
+        tera.set_escape_fn(xml_escape);
+        // The built in source is a constant, failing to parse it is a bug in n4 itself
+        tera.add_raw_template(DEFAULT_TEMPLATE, DEFAULT_TEMPLATE_SOURCE)
+            .expect("built in default template parses");
+        Templates { tera }
+    }
+
+    /// Every *.html under template_dir, template names are their paths relative to it like `blog/post.html`.
+    /// They're added on top of the built in default so they can extend it, or replace it with a `default.html`.
+    pub fn load(template_dir: &Path) -> N4Result<Self> {
+        if !template_dir.is_dir() {
+            return Err(N4Error::InvalidConfig(format!(
+                "Template dir is not a directory: {}",
+                template_dir.to_string_lossy()
+            )));
+        }
+        let mut files: Vec<(PathBuf, Option<String>)> = Vec::new();
+        collect_template_files(template_dir, template_dir, &mut files)?;
+        let mut templates = Templates::builtin();
+        templates
+            .tera
+            .add_template_files(files)
+            .map_err(N4Error::Template)?;
+        Ok(templates)
+    }
+
+    pub fn has_template(&self, name: &str) -> bool {
+        let name = template_file_name(name);
+        self.tera.get_template_names().any(|x| x == name)
+    }
+
+    /// The template chain for a page, page override then the section's index override for an `index` page, then
+    /// section template then the default
+    pub fn resolve(&self, page: &PageContent) -> String {
+        let index_override = if page.web_path.file_name() == Some("index") {
+            page.section_meta.template_override.as_str()
+        } else {
+            ""
+        };
+        [
+            page.meta.template_override.as_str(),
+            index_override,
+            page.section_meta.section_template.as_str(),
+        ]
+        .iter()
+        .filter(|x| !x.is_empty())
+        .map(|x| template_file_name(x))
+        .find(|x| self.has_template(x))
+        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string())
+    }
+
+    /// Renders a page through its template, sections are its section metas nearest first for the includes
+    pub fn render_page(
+        &self,
+        page: &PageContent,
+        menus: &HashMap<String, MenuItem>,
+        sections: &[MenuItemMeta],
+        site: &Site,
+    ) -> N4Result<String> {
+        let mut page = page.clone();
+        page.render_markdown();
+        let mut context = Context::new();
+        context.insert("page", &page);
+        context.insert("menus", menus);
+        context.insert("includes", &PageIncludes::for_page(&page, sections));
+        let mut site_context: HashMap<&str, &str> = HashMap::new();
+        site_context.insert("prod_host", &site.config().prod_host);
+        context.insert("site", &site_context);
+        self.tera
+            .render(&self.resolve(&page), &context)
+            .map_err(N4Error::Template)
+    }
+}
+
+fn collect_template_files(
+    template_dir: &Path,
+    dir: &Path,
+    files: &mut Vec<(PathBuf, Option<String>)>,
+) -> N4Result<()> {
+    for entry in fs::read_dir(dir).map_err(|why| N4Error::io(dir, why))? {
+        let path = entry.map_err(|why| N4Error::io(dir, why))?.path();
+        if path.is_dir() {
+            collect_template_files(template_dir, &path, files)?;
+        } else if path.extension().is_some_and(|x| x == "html") {
+            let name = path
+                .strip_prefix(template_dir)
+                .map(|x| x.to_string_lossy().replace('\\', "/"))
+                .ok();
+            files.push((path, name));
+        }
+    }
+    Ok(())
+}
+
+// Meta files name templates without the extension, `article` is `article.html`
+fn template_file_name(name: &str) -> String {
+    if name.ends_with(".html") {
+        name.to_string()
+    } else {
+        format!("{}.html", name)
+    }
+}
+
+impl Site {
+    /// Templates from the configured template_dir, or just the built in default when there isn't one.  They're
+    /// loaded the first time this is called and kept for the life of the Site, a failed load is tried again.
+    pub fn templates(&self) -> N4Result<&Templates> {
+        if let Some(templates) = self.templates.get() {
+            return Ok(templates);
+        }
+        let templates = if self.config().template_dir.is_empty() {
+            Templates::builtin()
+        } else {
+            Templates::load(Path::new(&self.config().template_dir))?
+        };
+        Ok(self.templates.get_or_init(|| templates))
+    }
+
+    /// Reads a page and renders it through its template with the site menus.  The menus walk the whole tree, a
+    /// server should use ContentIndex::render_page which has them cached.
+    pub fn render_page(&self, this_path: &str) -> N4Result<String> {
+        let page = self.read_single_page(this_path)?;
+        let menus = self.tree_to_menus(self.generate_content_state()?)?;
+        let sections = self.ancestor_section_metas(&page.web_path);
+        self.templates()?
+            .render_page(&page, &menus, &sections, self)
+    }
+}
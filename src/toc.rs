@@ -0,0 +1,144 @@
+//! Table of contents from markdown headings
+//!
+//! With `MarkdownOptions.toc` on a page gets `PageContent.toc`, one entry per heading in document order, and every
+//! rendered heading gets the entry's anchor as its id.  Anchors are slugs of the heading text, lowercased with runs
+//! of anything that isn't a letter or digit turned into a single `-`.  A slug that's already taken gets `-1`, `-2`,
+//! ... so anchors only change when the headings before them do.
+use std::collections::HashSet;
+
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::options::Plugins;
+use comrak::{format_html_with_plugins, parse_document, Arena, Options};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::markdown::MarkdownOptions;
+
+// Private use characters, comrak leaves them alone so they survive rendering to mark where each heading starts
+const MARKER: char = '\u{E000}';
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TocEntry {
+    /// 1 to 6, the h1 to h6 the heading renders as
+    pub level: u32,
+    pub text: String,
+    /// The id of the rendered heading, without the #
+    pub anchor: String,
+}
+
+/// The table of contents for markdown source
+pub fn markdown_toc(source: &str, options: &MarkdownOptions) -> Vec<TocEntry> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, source, &options.to_comrak());
+    toc_entries(root)
+}
+
+/// Renders a parsed document with the TOC anchors as ids on the headings
+pub(crate) fn render_with_anchors<'a>(
+    arena: &'a Arena<'a>,
+    root: &'a AstNode<'a>,
+    options: &Options,
+    plugins: &Plugins,
+) -> String {
+    let entries = toc_entries(root);
+    let headings: Vec<&AstNode> = root.descendants().filter(|x| is_heading(x)).collect();
+    for (number, heading) in headings.into_iter().enumerate() {
+        let marker = arena.alloc(AstNode::from(NodeValue::Text(marker(number).into())));
+        heading.prepend(marker);
+    }
+    let mut html = String::new();
+    // Writing to a String can't fail
+    format_html_with_plugins(root, options, &mut html, plugins).ok();
+
+    for (number, entry) in entries.iter().enumerate() {
+        let marker = marker(number);
+        let position = match html.find(&marker) {
+            Some(val) => val,
+            None => continue,
+        };
+        html.replace_range(position..position + marker.len(), "");
+        // header_ids puts comrak's own anchor link first, so look back for the opening tag
+        let open_tag = format!("<h{}", entry.level);
+        if let Some(start) = html[..position].rfind(&open_tag) {
+            html.insert_str(start + open_tag.len(), &format!(" id=\"{}\"", entry.anchor));
+        }
+    }
+    html
+}
+
+/// Slug for an anchor, empty text gives `section`
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for character in text.chars().flat_map(|x| x.to_lowercase()) {
+        if character.is_alphanumeric() {
+            slug.push(character);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        String::from("section")
+    } else {
+        slug.to_string()
+    }
+}
+
+fn toc_entries<'a>(root: &'a AstNode<'a>) -> Vec<TocEntry> {
+    let mut used: HashSet<String> = HashSet::new();
+    let mut entries: Vec<TocEntry> = Vec::new();
+    for node in root.descendants() {
+        let level = match node.data.borrow().value {
+            NodeValue::Heading(ref heading) => u32::from(heading.level),
+            _ => continue,
+        };
+        let text = heading_text(node);
+        let slug = slugify(&text);
+        let mut anchor = slug.clone();
+        let mut count = 0;
+        while used.contains(&anchor) {
+            count += 1;
+            anchor = format!("{}-{}", slug, count);
+        }
+        used.insert(anchor.clone());
+        entries.push(TocEntry {
+            level,
+            text,
+            anchor,
+        });
+    }
+    entries
+}
+
+fn heading_text<'a>(heading: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for node in heading.descendants() {
+        match node.data.borrow().value {
+            NodeValue::Text(ref literal) => text.push_str(literal),
+            NodeValue::Code(ref code) => text.push_str(&code.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+fn is_heading<'a>(node: &'a AstNode<'a>) -> bool {
+    matches!(node.data.borrow().value, NodeValue::Heading(_))
+}
+
+fn marker(number: usize) -> String {
+    format!("{}{}{}", MARKER, number, MARKER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::slugify;
+
+    #[test]
+    fn slugs_are_lowercase_and_dashed() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  `n4` -- setup  "), "n4-setup");
+        assert_eq!(slugify("Ünïcode Title"), "ünïcode-title");
+        assert_eq!(slugify("!!!"), "section");
+    }
+}
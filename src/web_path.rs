@@ -0,0 +1,161 @@
+//! Validated web paths
+//!
+//! Anything that comes in from a URL or a content_list goes through `WebPath::parse` before it gets near the
+//! filesystem, so the content root can't be escaped with `..`, absolute components or NUL bytes.  Symlinks are
+//! checked separately at read time by `Site::checked_path` since that needs the filesystem.
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::{N4Error, N4Result};
+
+/// Serializes as its string form and is parsed again on the way back in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct WebPath {
+    segments: Vec<String>,
+}
+
+impl Default for WebPath {
+    fn default() -> Self {
+        WebPath::root()
+    }
+}
+
+impl WebPath {
+    /// The site root, "/"
+    pub fn root() -> Self {
+        WebPath {
+            segments: Vec::new(),
+        }
+    }
+
+    /// Normalizes a raw web path, empty and "." segments are dropped, anything that could leave the content root
+    /// is rejected with N4Error::PathEscape
+    pub fn parse(raw: &str) -> N4Result<Self> {
+        if raw.contains('\0') {
+            return Err(N4Error::PathEscape(raw.replace('\0', "\\0")));
+        }
+        let mut segments: Vec<String> = Vec::new();
+        for segment in raw.split('/') {
+            if segment.is_empty() || segment == "." {
+                continue;
+            }
+            if segment == ".." || segment.contains('\\') || !is_single_normal_component(segment) {
+                return Err(N4Error::PathEscape(raw.to_string()));
+            }
+            segments.push(segment.to_string());
+        }
+        Ok(WebPath { segments })
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// A `.` file or directory anywhere along the path, those stay out of the sitemap, listings and the export
+    pub fn is_hidden(&self) -> bool {
+        self.segments.iter().any(|x| x.starts_with('.'))
+    }
+
+    /// Last segment, the file stem for content
+    pub fn file_name(&self) -> Option<&str> {
+        self.segments.last().map(|x| x.as_str())
+    }
+
+    /// The containing directory, the root is its own parent
+    pub fn parent(&self) -> WebPath {
+        let mut segments = self.segments.clone();
+        segments.pop();
+        WebPath { segments }
+    }
+
+    /// True for this path and anything below it
+    pub fn starts_with(&self, other: &WebPath) -> bool {
+        self.segments.starts_with(&other.segments)
+    }
+
+    pub fn join(&self, segment: &str) -> N4Result<WebPath> {
+        WebPath::parse(&format!("{}/{}", self, segment))
+    }
+
+    /// Local filesystem path under the given content root, no extension is added
+    pub fn to_local(&self, root: &Path) -> PathBuf {
+        let mut local = root.to_path_buf();
+        for segment in &self.segments {
+            local.push(segment);
+        }
+        local
+    }
+}
+
+// On unix this only catches oddities like a drive prefix, everything else is already split on '/'
+fn is_single_normal_component(segment: &str) -> bool {
+    let mut components = Path::new(segment).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+impl fmt::Display for WebPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.segments.join("/"))
+    }
+}
+
+impl FromStr for WebPath {
+    type Err = N4Error;
+
+    fn from_str(raw: &str) -> N4Result<Self> {
+        WebPath::parse(raw)
+    }
+}
+
+impl TryFrom<String> for WebPath {
+    type Error = N4Error;
+
+    fn try_from(raw: String) -> N4Result<Self> {
+        WebPath::parse(&raw)
+    }
+}
+
+impl From<WebPath> for String {
+    fn from(web_path: WebPath) -> Self {
+        web_path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WebPath;
+
+    #[test]
+    fn normalizes_delimiters_and_dots() {
+        let web_path = WebPath::parse("//docs/./intro/").unwrap();
+        assert_eq!(web_path.to_string(), "/docs/intro");
+        assert_eq!(web_path.parent().to_string(), "/docs");
+    }
+
+    #[test]
+    fn hidden_anywhere_along_the_path() {
+        assert!(WebPath::parse("/.drafts/idea").unwrap().is_hidden());
+        assert!(WebPath::parse("/docs/.notes").unwrap().is_hidden());
+        assert!(!WebPath::parse("/docs/intro.v2").unwrap().is_hidden());
+        assert!(!WebPath::root().is_hidden());
+    }
+
+    #[test]
+    fn rejects_escapes() {
+        for raw in &["/../../etc/passwd", "/docs/../..", "/a\0b", "/a\\..\\b"] {
+            assert!(WebPath::parse(raw).is_err(), "{} should be rejected", raw);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Wiki style links
+//!
+//! With `MarkdownOptions.wiki_links` on, `[[Page Title]]` links to the page whose `ContentMeta.title` matches, case
+//! and surrounding spaces don't matter, and `[[/section/page]]` links to the page at that web path.
+//! `[[target|label]]` sets the link text and a `#fragment` on the target is kept.  Links point at the page's
+//! `ContentMeta.path` when it's set and its web path otherwise, the same location the sitemap uses.  A target
+//! nothing matches renders as `<span class="missing-page">` around the label so it stands out instead of going
+//! nowhere, and so does a page that isn't listed, a draft say.  When two pages share a title the one with the first
+//! web path wins.
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use comrak::nodes::{AstNode, NodeLink, NodeValue};
+use comrak::Arena;
+
+use crate::error::N4Result;
+use crate::file_tree::tree_stamp;
+use crate::sitemap::xml_escape;
+use crate::web_path::WebPath;
+use crate::{content_location, ContentMeta, PageContent, PageSource, Site, SiteConfig};
+
+/// Where wiki link targets go, by lowercased title and by web path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WikiIndex {
+    titles: HashMap<String, String>,
+    paths: HashMap<String, String>,
+}
+
+impl WikiIndex {
+    /// Adds a page, a title that's already taken keeps its first page
+    pub fn insert(&mut self, web_path: &WebPath, meta: &ContentMeta) {
+        let location = content_location(web_path, meta);
+        let title = title_key(&meta.title);
+        if !title.is_empty() {
+            self.titles.entry(title).or_insert_with(|| location.clone());
+        }
+        self.paths.insert(web_path.to_string(), location.clone());
+        self.paths.entry(location.clone()).or_insert(location);
+    }
+
+    /// The href for a wiki link target, None when there's no such page
+    pub fn resolve(&self, target: &str) -> Option<String> {
+        let (page, fragment) = match target.split_once('#') {
+            Some((page, fragment)) => (page.trim(), Some(fragment.trim())),
+            None => (target.trim(), None),
+        };
+        let location = if page.starts_with('/') {
+            self.paths.get(&WebPath::parse(page).ok()?.to_string())?
+        } else {
+            self.titles.get(&title_key(page))?
+        };
+        Some(match fragment {
+            Some(val) if !val.is_empty() => format!("{}#{}", location, val),
+            _ => location.clone(),
+        })
+    }
+}
+
+/// A wiki index with the tree stamp it was built at, see Site::shared_wiki_index
+pub(crate) type SharedWiki = Mutex<Option<(u64, Arc<WikiIndex>)>>;
+
+impl Site {
+    /// The wiki link index over every content item, a page whose meta can't be read is logged and left out and so are
+    /// drafts, scheduled and expired pages unless previewing, export doesn't write them.  Metas are only peeked at,
+    /// building the index never writes defaults into the content tree.
+    pub fn wiki_index(&self) -> N4Result<WikiIndex> {
+        let mut web_paths = self.content_items(&self.generate_content_state()?)?;
+        web_paths.sort();
+        let mut index = WikiIndex::default();
+        for web_path in web_paths {
+            let meta = self
+                .checked_path(&web_path, Some("content_meta"))
+                .and_then(|x| self.peek_content_meta(&x));
+            match meta {
+                Ok(meta) if self.config().is_listed(&meta) => index.insert(&web_path, &meta),
+                Ok(_) => {}
+                Err(why) => println!("Wiki index failure. {}", why),
+            }
+        }
+        Ok(index)
+    }
+
+    /// The wiki index single page reads link against.  It's shared between reads and only built again when the
+    /// tree stamp, every file's path, size and modified time, has moved since, so a page written since the last
+    /// build still resolves.  That's a walk of the tree on every read without opening any files, a server that
+    /// wants to skip even that should read through ContentIndex.
+    pub(crate) fn shared_wiki_index(&self) -> N4Result<Arc<WikiIndex>> {
+        let stamp = tree_stamp(Path::new(&self.local_path()));
+        // A panic mid build leaves nothing half written behind, the cache is still good to use
+        let mut cached = self.wiki.lock().unwrap_or_else(|x| x.into_inner());
+        if let Some((built_at, wiki)) = cached.as_ref() {
+            if *built_at == stamp {
+                return Ok(wiki.clone());
+            }
+        }
+        let wiki = Arc::new(self.wiki_index()?);
+        *cached = Some((stamp, wiki.clone()));
+        Ok(wiki)
+    }
+
+    /// The site's pages with one wiki index shared between them, for anything that reads the whole tree
+    pub(crate) fn wiki_pages(&self) -> WikiPages<'_> {
+        WikiPages {
+            site: self,
+            wiki: OnceCell::new(),
+        }
+    }
+}
+
+/// Reads pages from a Site the way Site::page_parts does, except the wiki index is built once, the first time a
+/// page with wiki links asks for it, rather than once per page
+pub(crate) struct WikiPages<'a> {
+    site: &'a Site,
+    wiki: OnceCell<Arc<WikiIndex>>,
+}
+
+impl WikiPages<'_> {
+    pub(crate) fn wiki_index(&self) -> N4Result<Arc<WikiIndex>> {
+        if let Some(wiki) = self.wiki.get() {
+            return Ok(wiki.clone());
+        }
+        let wiki = Arc::new(self.site.wiki_index()?);
+        Ok(self.wiki.get_or_init(|| wiki).clone())
+    }
+}
+
+impl PageSource for WikiPages<'_> {
+    fn site_config(&self) -> &SiteConfig {
+        self.site.config()
+    }
+
+    fn has_content(&self, web_path: &WebPath) -> bool {
+        self.site.has_content(web_path)
+    }
+
+    fn page_parts(&self, web_path: &WebPath) -> N4Result<PageContent> {
+        self.site.read_page_parts(web_path, &|| self.wiki_index())
+    }
+}
+
+/// Turns the WikiLink nodes comrak parsed into links, or missing page markers
+pub(crate) fn link_wiki_nodes<'a>(arena: &'a Arena<'a>, root: &'a AstNode<'a>, index: &WikiIndex) {
+    let wiki_links: Vec<&AstNode> = root
+        .descendants()
+        .filter(|x| matches!(x.data.borrow().value, NodeValue::WikiLink(_)))
+        .collect();
+    for node in wiki_links {
+        let target = match node.data.borrow().value {
+            NodeValue::WikiLink(ref link) => link.url.clone(),
+            _ => continue,
+        };
+        match index.resolve(&target) {
+            Some(url) => {
+                node.data.borrow_mut().value = NodeValue::Link(Box::new(NodeLink {
+                    url,
+                    title: String::new(),
+                }));
+            }
+            None => {
+                let open = format!(
+                    "<span class=\"missing-page\" title=\"Missing page: {}\">",
+                    xml_escape(&target)
+                );
+                node.insert_before(arena.alloc(AstNode::from(NodeValue::Raw(open))));
+                while let Some(child) = node.first_child() {
+                    node.insert_before(child);
+                }
+                node.insert_before(
+                    arena.alloc(AstNode::from(NodeValue::Raw(String::from("</span>")))),
+                );
+                node.detach();
+            }
+        }
+    }
+}
+
+fn title_key(title: &str) -> String {
+    title.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WikiIndex;
+    use crate::web_path::WebPath;
+    use crate::ContentMeta;
+
+    #[test]
+    fn titles_and_paths_resolve() {
+        let mut index = WikiIndex::default();
+        let meta = ContentMeta {
+            title: String::from("Getting Started"),
+            path: String::from("/docs/start"),
+            ..ContentMeta::default()
+        };
+        index.insert(&WebPath::parse("/docs/start").unwrap(), &meta);
+        assert_eq!(index.resolve(" getting started ").unwrap(), "/docs/start");
+        assert_eq!(
+            index.resolve("/docs/start#install").unwrap(),
+            "/docs/start#install"
+        );
+        assert!(index.resolve("Finishing").is_none());
+        assert!(index.resolve("/docs/finish").is_none());
+    }
+}
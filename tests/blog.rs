@@ -0,0 +1,146 @@
+mod common;
+
+use common::TestSite;
+use n4::N4Error;
+use serde_json::json;
+
+fn blog_site() -> TestSite {
+    let test = TestSite::new();
+    test.write_menu_meta("blog", json!({ "listing": "blog", "page_size": 2 }));
+    test.write("blog/index.md", "# Blog\n");
+    for (stem, published) in [
+        ("hello", "2023-11-20"),
+        ("winter", "2023-12-05T09:00:00Z"),
+        ("new-year", "2024-01-01 08:30"),
+        ("spring", "2024-03-15"),
+        ("march-again", "2024-03-02"),
+    ] {
+        test.write(
+            &format!("blog/{}.md", stem),
+            &format!(
+                "---\ntitle: {}\npublished: {}\n---\nPost\n",
+                stem, published
+            ),
+        );
+    }
+    test.write("blog/drafts/idea.md", "Not a post of the blog\n");
+    test
+}
+
+#[test]
+fn posts_are_newest_first() {
+    let test = blog_site();
+    let titles: Vec<String> = test
+        .site
+        .blog_posts("/blog")
+        .unwrap()
+        .into_iter()
+        .map(|x| x.title)
+        .collect();
+    assert_eq!(
+        titles,
+        ["spring", "march-again", "new-year", "winter", "hello"]
+    );
+
+    let metas = test.site.read_full_dir_sorted("/blog").unwrap();
+    let meta_titles: Vec<String> = metas.into_iter().map(|x| x.title).collect();
+    // Same posts in the same order, the index page is the listing and not a post
+    assert_eq!(meta_titles, titles);
+}
+
+#[test]
+fn undated_posts_go_by_file_time_in_both_listings() {
+    let test = blog_site();
+    test.write("blog/undated.md", "# No published date\n");
+
+    let titles: Vec<String> = test
+        .site
+        .blog_posts("/blog")
+        .unwrap()
+        .into_iter()
+        .map(|x| x.title)
+        .collect();
+    // Just written, so newer than any of the dated ones
+    assert_eq!(titles[0], "undated");
+    let meta_titles: Vec<String> = test
+        .site
+        .read_full_dir_sorted("/blog")
+        .unwrap()
+        .into_iter()
+        .map(|x| x.title)
+        .collect();
+    assert_eq!(meta_titles, titles);
+}
+
+#[test]
+fn listing_pages_paginate() {
+    let test = blog_site();
+    let first = test.site.blog_page("/blog", 1).unwrap();
+    assert_eq!((first.total_pages, first.total_posts), (3, 5));
+    assert_eq!(first.posts.len(), 2);
+    assert_eq!(first.posts[0].meta.title, "spring");
+    assert_eq!((first.previous_page, first.next_page), (None, Some(2)));
+
+    let last = test.site.blog_page("/blog", 3).unwrap();
+    assert_eq!(last.posts.len(), 1);
+    assert_eq!(last.posts[0].meta.title, "hello");
+    assert_eq!((last.previous_page, last.next_page), (Some(2), None));
+
+    assert!(test.site.blog_page("/blog", 4).unwrap_err().is_not_found());
+    assert!(test.site.blog_page("/blog", 0).unwrap_err().is_not_found());
+}
+
+#[test]
+fn archives_group_by_year_and_month() {
+    let test = blog_site();
+    let archive = test.site.blog_archive("/blog").unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!((archive[0].year, archive[0].count), (2024, 3));
+    let months: Vec<u32> = archive[0].months.iter().map(|x| x.month).collect();
+    assert_eq!(months, [3, 1]);
+    assert_eq!(archive[0].months[0].posts.len(), 2);
+    assert_eq!((archive[1].year, archive[1].count), (2023, 2));
+}
+
+#[test]
+fn posts_link_to_their_neighbours() {
+    let test = blog_site();
+    let links = test.site.post_links("/blog/new-year").unwrap();
+    assert_eq!(links.previous.unwrap().title, "winter");
+    assert_eq!(links.next.unwrap().location, "/blog/march-again");
+
+    let newest = test.site.post_links("/blog/spring").unwrap();
+    assert!(newest.next.is_none());
+    assert_eq!(newest.previous.unwrap().title, "march-again");
+}
+
+#[test]
+fn only_blog_sections_have_blog_listings() {
+    let test = blog_site();
+    test.write("docs/intro.md", "# Intro\n");
+    assert!(matches!(
+        test.site.blog_posts("/docs"),
+        Err(N4Error::InvalidConfig(_))
+    ));
+    let links = test.site.post_links("/docs/intro").unwrap();
+    assert!(links.previous.is_none() && links.next.is_none());
+}
+
+#[test]
+fn bad_dates_are_meta_errors() {
+    let test = TestSite::new();
+    test.write("post.md", "---\npublished: last tuesday\n---\nPost\n");
+    assert!(test.site.read_single_page("/post").is_err());
+}
+
+#[test]
+fn an_unreadable_post_is_left_out() {
+    let test = blog_site();
+    test.write("blog/broken.md", "Broken\n");
+    test.write("blog/broken.content_meta", "{ not json");
+
+    let posts = test.site.blog_posts("/blog").unwrap();
+    assert_eq!(posts.len(), 5);
+    assert!(posts.iter().all(|x| x.title != "broken"));
+    assert_eq!(test.site.blog_archive("/blog").unwrap()[0].count, 3);
+}
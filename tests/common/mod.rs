@@ -0,0 +1,72 @@
+//! Shared helpers for the integration tests, builds throwaway content trees in a temp dir
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+
+use n4::{ContentMeta, MenuItemMeta, Site, SiteConfig};
+use serde_json::Value;
+use tempfile::TempDir;
+
+pub struct TestSite {
+    pub dir: TempDir,
+    pub site: Site,
+}
+
+impl TestSite {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().expect("temp dir");
+        fs::create_dir_all(dir.path().join("content")).expect("content dir");
+        let site = Site::new(test_config(&dir));
+        TestSite { dir, site }
+    }
+
+    /// Absolute path of a file relative to the content root
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.dir.path().join("content").join(relative)
+    }
+
+    /// Writes a file relative to the content root, creating parent directories as needed
+    pub fn write(&self, relative: &str, contents: &str) {
+        let path = self.path(relative);
+        fs::create_dir_all(path.parent().unwrap()).expect("parent dir");
+        fs::write(path, contents).expect("write test file");
+    }
+
+    /// Writes a .content_meta file made of the defaults with the given JSON fields laid over them
+    pub fn write_content_meta(&self, relative: &str, fields: Value) {
+        let defaults = serde_json::to_value(ContentMeta::default()).unwrap();
+        self.write(
+            &format!("{}.content_meta", relative),
+            &merged(defaults, fields).to_string(),
+        );
+    }
+
+    /// Same as write_content_meta for a directory's .menu_meta
+    pub fn write_menu_meta(&self, relative: &str, fields: Value) {
+        let defaults = serde_json::to_value(MenuItemMeta::default()).unwrap();
+        self.write(
+            &format!("{}.menu_meta", relative),
+            &merged(defaults, fields).to_string(),
+        );
+    }
+}
+
+fn merged(mut defaults: Value, fields: Value) -> Value {
+    if let (Some(target), Value::Object(fields)) = (defaults.as_object_mut(), fields) {
+        for (key, value) in fields {
+            target.insert(key, value);
+        }
+    }
+    defaults
+}
+
+pub fn test_config(dir: &TempDir) -> SiteConfig {
+    SiteConfig {
+        prod_host: String::from("https://example.com"),
+        xml_priority: String::from("0.64"),
+        base_dir: String::from("content/"),
+        local_content_dir: format!("{}/", dir.path().to_string_lossy()),
+        ..SiteConfig::default()
+    }
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::TestSite;
+use n4::{ContentEvent, ContentIndex, WebPath};
+use serde_json::json;
+
+#[test]
+fn serves_pages_from_the_cache() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "# Intro");
+    let index = ContentIndex::build(test.site.clone()).unwrap();
+
+    // Gone from disk, still served until an event says otherwise
+    std::fs::remove_file(test.path("docs/intro.md")).unwrap();
+    let page = index.read_single_page("/docs/intro").unwrap();
+    assert!(page.markdown.rendered_html().contains("<h1>Intro</h1>"));
+}
+
+#[test]
+fn modified_and_created_files_are_picked_up() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "# Intro");
+    let mut index = ContentIndex::build(test.site.clone()).unwrap();
+
+    test.write("docs/intro.md", "# Changed");
+    index
+        .apply(ContentEvent::Modified(test.path("docs/intro.md")))
+        .unwrap();
+    let page = index.read_single_page("/docs/intro").unwrap();
+    assert!(page.markdown.rendered_html().contains("<h1>Changed</h1>"));
+
+    test.write("docs/more/deep.md", "Deep");
+    index
+        .apply(ContentEvent::Created(test.path("docs/more")))
+        .unwrap();
+    assert!(index.read_single_page("/docs/more/deep").is_ok());
+    assert!(index.tree().directories["docs"]
+        .directories
+        .contains_key("more"));
+}
+
+#[test]
+fn removed_files_and_directories_are_dropped() {
+    let test = TestSite::new();
+    test.write("a.md", "A");
+    test.write("docs/intro.md", "Intro");
+    let mut index = ContentIndex::build(test.site.clone()).unwrap();
+
+    std::fs::remove_file(test.path("a.md")).unwrap();
+    index
+        .apply(ContentEvent::Removed(test.path("a.md")))
+        .unwrap();
+    assert!(index.read_single_page("/a").unwrap_err().is_not_found());
+
+    std::fs::remove_dir_all(test.path("docs")).unwrap();
+    index
+        .apply(ContentEvent::Removed(test.path("docs")))
+        .unwrap();
+    assert!(index
+        .page(&WebPath::parse("/docs/intro").unwrap())
+        .is_none());
+    assert!(!index.tree().directories.contains_key("docs"));
+}
+
+#[test]
+fn section_meta_changes_reach_cached_pages() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "Intro");
+    let mut index = ContentIndex::build(test.site.clone()).unwrap();
+
+    test.write_menu_meta("docs", json!({ "description": "Updated" }));
+    index
+        .apply(ContentEvent::Created(test.path("docs.menu_meta")))
+        .unwrap();
+    let page = index.read_single_page("/docs/intro").unwrap();
+    assert_eq!(page.section_meta.description, "Updated");
+    let menus = serde_json::to_value(index.menus().unwrap()).unwrap();
+    assert_eq!(menus["docs"]["menu_meta"]["description"], "Updated");
+}
+
+#[test]
+fn bad_meta_drops_only_that_page() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "# Intro");
+    test.write("docs/broken.md", "---\ntitle: [unclosed\n---\nBroken\n");
+    let mut index = ContentIndex::build(test.site.clone()).unwrap();
+    assert!(index.read_single_page("/docs/intro").is_ok());
+    assert!(index
+        .page(&WebPath::parse("/docs/broken").unwrap())
+        .is_none());
+
+    // A bad edit drops the page until it's fixed, the rest of the index is untouched
+    test.write("docs/intro.md", "---\nweight: heavy\n---\n# Intro\n");
+    index
+        .apply(ContentEvent::Modified(test.path("docs/intro.md")))
+        .unwrap();
+    assert!(index
+        .page(&WebPath::parse("/docs/intro").unwrap())
+        .is_none());
+    test.write("docs/broken.md", "---\ntitle: Fixed\n---\nFixed\n");
+    index
+        .apply(ContentEvent::Modified(test.path("docs/broken.md")))
+        .unwrap();
+    assert_eq!(
+        index.read_single_page("/docs/broken").unwrap().meta.title,
+        "Fixed"
+    );
+}
+
+#[test]
+fn sitemap_comes_from_the_cache() {
+    let test = TestSite::new();
+    test.write_menu_meta("docs", json!({ "sitemap_priority": 0.3 }));
+    test.write("docs/a.md", "# A");
+    let index = ContentIndex::build(test.site.clone()).unwrap();
+
+    std::fs::remove_file(test.path("docs/a.md")).unwrap();
+    let entries = index.generate_sitemap().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].location, "https://example.com/docs/a");
+    assert_eq!(entries[0].priority, "0.3");
+}
+
+#[test]
+fn an_unreadable_file_is_left_out_of_the_build() {
+    let test = TestSite::new();
+    test.write("good.md", "# Good");
+    // Not UTF-8, reading it is an IO error like a missing permission would be
+    std::fs::write(test.path("bad.md"), [0xff, 0xfe, 0x00]).unwrap();
+
+    let index = ContentIndex::build(test.site.clone()).unwrap();
+    assert!(index.read_single_page("/good").is_ok());
+    assert!(index.read_single_page("/bad").unwrap_err().is_not_found());
+}
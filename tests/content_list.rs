@@ -0,0 +1,45 @@
+mod common;
+
+use common::TestSite;
+use serde_json::json;
+
+#[test]
+fn self_reference_is_left_out() {
+    let test = TestSite::new();
+    test.write("a.md", "A");
+    test.write_content_meta("a", json!({ "content_list": ["/a"] }));
+
+    let page = test.site.read_single_page("/a").unwrap();
+    assert!(page.list.is_empty());
+}
+
+#[test]
+fn mutual_references_stop_at_the_cycle() {
+    let test = TestSite::new();
+    test.write("a.md", "A");
+    test.write("b.md", "B");
+    test.write_content_meta("a", json!({ "content_list": ["/b"] }));
+    test.write_content_meta("b", json!({ "content_list": ["/a"] }));
+
+    let page = test.site.read_single_page("/a").unwrap();
+    assert_eq!(page.list.len(), 1);
+    assert!(page.list[0].markdown.rendered_html().contains("B"));
+    assert!(page.list[0].list.is_empty());
+}
+
+#[test]
+fn nesting_stops_at_max_depth() {
+    let mut test = TestSite::new();
+    let mut config = test.site.config().clone();
+    config.content_list_max_depth = 2;
+    test.site = n4::Site::new(config);
+    for (page, next) in &[("a", "b"), ("b", "c"), ("c", "d"), ("d", "a")] {
+        test.write(&format!("{}.md", page), page);
+        test.write_content_meta(page, json!({ "content_list": [format!("/{}", next)] }));
+    }
+
+    let page = test.site.read_single_page("/a").unwrap();
+    assert!(page.list[0].markdown.rendered_html().contains("b"));
+    assert!(page.list[0].list[0].markdown.rendered_html().contains("c"));
+    assert!(page.list[0].list[0].list.is_empty());
+}
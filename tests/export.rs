@@ -0,0 +1,113 @@
+mod common;
+
+use common::TestSite;
+
+#[test]
+fn exports_pages_sitemap_and_robots() {
+    let test = TestSite::new();
+    test.write("index.md", "# Home");
+    test.write("about.md", "# About");
+    test.write("docs/intro.html", "<p>Intro</p>");
+    let out = tempfile::tempdir().unwrap();
+
+    let report = test.site.export_site(out.path()).unwrap();
+    assert!(report.is_success(), "{}", report);
+    assert_eq!(report.pages_written.len(), 3);
+
+    let home = std::fs::read_to_string(out.path().join("index.html")).unwrap();
+    assert!(home.contains("<h1>Home</h1>"));
+    assert!(out.path().join("about/index.html").exists());
+    let intro = std::fs::read_to_string(out.path().join("docs/intro/index.html")).unwrap();
+    assert!(intro.contains("<p>Intro</p>"));
+    assert!(out.path().join("sitemap.xml").exists());
+    assert!(out.path().join("robots.txt").exists());
+}
+
+#[test]
+fn a_bad_page_is_reported_and_the_rest_exported() {
+    let test = TestSite::new();
+    test.write("good.md", "Good");
+    test.write("bad.md", "Bad");
+    test.write("bad.content_meta", "{ not json");
+    let out = tempfile::tempdir().unwrap();
+
+    let report = test.site.export_site(out.path()).unwrap();
+    assert_eq!(report.pages_written.len(), 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].web_path, "/bad");
+}
+
+#[test]
+fn pages_landing_on_the_same_file_are_reported() {
+    let test = TestSite::new();
+    test.write("docs.md", "# Docs page");
+    test.write("docs/index.md", "# Docs index");
+    let out = tempfile::tempdir().unwrap();
+
+    let report = test.site.export_site(out.path()).unwrap();
+    assert_eq!(report.pages_written.len(), 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].web_path, "/docs/index");
+    let docs = std::fs::read_to_string(out.path().join("docs/index.html")).unwrap();
+    assert!(docs.contains("<h1>Docs page</h1>"));
+}
+
+#[test]
+fn index_pages_are_listed_at_their_directory() {
+    let test = TestSite::new();
+    test.write("index.md", "# Home");
+    test.write("docs/index.md", "# Docs");
+    let mut locations: Vec<String> = test
+        .site
+        .generate_sitemap()
+        .unwrap()
+        .into_iter()
+        .map(|x| x.location)
+        .collect();
+    locations.sort();
+    assert_eq!(
+        locations,
+        ["https://example.com/", "https://example.com/docs/"]
+    );
+}
+
+#[test]
+fn hidden_pages_are_not_exported() {
+    let test = TestSite::new();
+    test.write("about.md", "# About");
+    test.write(".drafts/idea.md", "# Idea");
+    test.write(".notes.md", "# Notes");
+    let out = tempfile::tempdir().unwrap();
+
+    let report = test.site.export_site(out.path()).unwrap();
+    assert!(report.is_success(), "{}", report);
+    assert_eq!(report.pages_written.len(), 1);
+    assert!(!out.path().join(".drafts").exists());
+    assert!(!out.path().join(".notes").exists());
+}
+
+#[test]
+fn pages_are_exported_at_their_sitemap_location() {
+    let test = TestSite::new();
+    test.write("b.md", "---\npath: /blog/b\n---\n# B\n");
+    test.write("docs/index.md", "---\npath: /guide/index\n---\n# Guide\n");
+    let out = tempfile::tempdir().unwrap();
+
+    let report = test.site.export_site(out.path()).unwrap();
+    assert!(report.is_success(), "{}", report);
+    assert!(out.path().join("blog/b/index.html").exists());
+    assert!(!out.path().join("b").exists());
+    assert!(out.path().join("guide/index.html").exists());
+    let mut locations: Vec<String> = test
+        .site
+        .generate_sitemap()
+        .unwrap()
+        .into_iter()
+        .map(|x| x.location)
+        .collect();
+    locations.sort();
+    assert_eq!(
+        locations,
+        ["https://example.com/blog/b", "https://example.com/guide/"]
+    );
+}
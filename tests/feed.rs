@@ -0,0 +1,109 @@
+mod common;
+
+use common::TestSite;
+use n4::{FeedConfig, FeedFormat, Site};
+use serde_json::json;
+
+fn feed_site() -> TestSite {
+    let test = TestSite::new();
+    test.write_menu_meta("blog", json!({ "description": "Notes & news" }));
+    test.write("blog/index.md", "# Blog\n");
+    test.write(
+        "blog/older.md",
+        "---\ntitle: Older\nauthor: Sam\ndescription: The first one\npublished: 2024-01-02\n---\n# Older\n",
+    );
+    test.write(
+        "blog/newer.md",
+        "---\ntitle: Fish & Chips\nauthor: Sam\npublished: 2024-02-03T10:00:00Z\n---\nHot *food*\n",
+    );
+    test.write("blog/2024/deep.html", "<p>Deep</p>");
+    test.write_content_meta(
+        "blog/2024/deep",
+        json!({ "title": "Deep", "path": "/blog/2024/deep", "published": "2023-06-01" }),
+    );
+    test.write("blog/hidden.md", "---\nnoindex: true\n---\nHidden\n");
+    test.write(
+        "about.md",
+        "---\ntitle: About\npublished: 2020-01-01\n---\nAbout\n",
+    );
+    test
+}
+
+#[test]
+fn atom_entries_are_newest_first() {
+    let test = feed_site();
+    let xml = test.site.generate_feed("/blog", FeedFormat::Atom).unwrap();
+
+    assert!(xml.starts_with(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">"
+    ));
+    assert!(xml.contains("<title>https://example.com: blog</title>"));
+    assert!(xml.contains("<subtitle>Notes &amp; news</subtitle>"));
+    assert!(xml.contains("<updated>"));
+    let newer = xml.find("<title>Fish &amp; Chips</title>").unwrap();
+    let older = xml.find("<title>Older</title>").unwrap();
+    let deep = xml.find("<title>Deep</title>").unwrap();
+    assert!(newer < older && older < deep);
+    assert!(xml.contains("<id>https://example.com/blog/older</id>"));
+    assert!(xml.contains("<published>2024-01-02T00:00:00+00:00</published>"));
+    assert!(xml.contains("<author><name>Sam</name></author>"));
+    assert!(xml.contains("<summary>The first one</summary>"));
+    // Rendered and then escaped into the content element
+    assert!(xml.contains("<content type=\"html\">&lt;p&gt;Hot &lt;em&gt;food&lt;/em&gt;&lt;/p&gt;"));
+
+    assert!(!xml.contains("Hidden"));
+    assert!(!xml.contains("<title>Blog</title>"));
+    assert!(!xml.contains("About"));
+}
+
+#[test]
+fn rss_has_channel_and_items() {
+    let test = feed_site();
+    let xml = test.site.generate_feed("/blog", FeedFormat::Rss).unwrap();
+
+    assert!(xml.contains("<rss version=\"2.0\""));
+    assert!(xml.contains("<link>https://example.com/blog/</link>"));
+    assert!(xml.contains("<description>Notes &amp; news</description>"));
+    assert!(xml.contains("<guid isPermaLink=\"true\">https://example.com/blog/newer</guid>"));
+    assert!(xml.contains("<pubDate>Sat, 3 Feb 2024 10:00:00 +0000</pubDate>"));
+    assert!(xml.contains("<dc:creator>Sam</dc:creator>"));
+    assert!(xml.contains("<content:encoded>&lt;p&gt;Deep&lt;/p&gt;</content:encoded>"));
+    assert_eq!(xml.matches("<item>").count(), 3);
+}
+
+#[test]
+fn site_feed_and_item_count() {
+    let test = feed_site();
+    let mut config = test.site.config().clone();
+    config.feed = FeedConfig {
+        items: 2,
+        title: String::from("Example"),
+    };
+    let site = Site::new(config);
+
+    let xml = site.generate_feed("/", FeedFormat::Atom).unwrap();
+    assert!(xml.contains("<title>Example</title>"));
+    assert_eq!(xml.matches("<entry>").count(), 2);
+    assert!(xml.contains("<title>Fish &amp; Chips</title>"));
+    assert!(xml.contains("<title>Older</title>"));
+
+    let rss = site.generate_feed("/", FeedFormat::Rss).unwrap();
+    // No section description at the root, RSS needs one anyway
+    assert!(rss.contains("<description>Example</description>"));
+}
+
+#[test]
+fn a_bad_post_is_left_out_and_urls_are_encoded() {
+    let test = feed_site();
+    test.write("blog/broken.md", "Broken\n");
+    test.write("blog/broken.content_meta", "{ not json");
+    test.write(
+        "blog/c# tips.md",
+        "---\ntitle: Tips\npublished: 2024-03-01\n---\nTips\n",
+    );
+
+    let xml = test.site.generate_feed("/blog", FeedFormat::Atom).unwrap();
+    assert!(xml.contains("<id>https://example.com/blog/c%23%20tips</id>"));
+    assert!(xml.contains("<link href=\"https://example.com/blog/\"/>"));
+    assert_eq!(xml.matches("<entry>").count(), 4);
+}
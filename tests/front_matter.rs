@@ -0,0 +1,61 @@
+mod common;
+
+use common::TestSite;
+use n4::N4Error;
+use serde_json::json;
+
+#[test]
+fn yaml_front_matter_is_the_meta_and_not_the_body() {
+    let test = TestSite::new();
+    test.write(
+        "post.md",
+        "---\ntitle: Hello\nauthor: Someone\nweight: 7\n---\n# Body\n",
+    );
+
+    let page = test.site.read_single_page("/post").unwrap();
+    assert_eq!(page.meta.title, "Hello");
+    assert_eq!(page.meta.author, "Someone");
+    assert_eq!(page.meta.weight, 7);
+    assert_eq!(page.meta.path, "/post");
+    assert_eq!(page.markdown.rendered_html(), "<h1>Body</h1>\n");
+    assert!(!test.path("post.content_meta").exists());
+}
+
+#[test]
+fn toml_front_matter_overrides_the_sidecar() {
+    let test = TestSite::new();
+    test.write_content_meta(
+        "post",
+        json!({ "title": "From sidecar", "author": "Sidecar", "path": "/post" }),
+    );
+    test.write("post.md", "+++\ntitle = \"From front matter\"\n+++\nBody\n");
+
+    let page = test.site.read_single_page("/post").unwrap();
+    assert_eq!(page.meta.title, "From front matter");
+    assert_eq!(page.meta.author, "Sidecar");
+    assert_eq!(page.markdown.rendered_html(), "<p>Body</p>\n");
+}
+
+#[test]
+fn broken_front_matter_is_an_error() {
+    let test = TestSite::new();
+    test.write("post.md", "---\ntitle: [unclosed\n---\nBody\n");
+
+    match test.site.read_single_page("/post") {
+        Err(N4Error::FrontMatter { .. }) => {}
+        other => panic!("expected a FrontMatter error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn thematic_breaks_around_prose_are_body() {
+    let test = TestSite::new();
+    test.write("post.md", "---\n\nSome intro.\n\n---\n\n# Body\n");
+
+    let page = test.site.read_single_page("/post").unwrap();
+    assert_eq!(page.meta.title, "post");
+    assert_eq!(
+        page.markdown.rendered_html(),
+        "<hr>\n<p>Some intro.</p>\n<hr>\n<h1>Body</h1>\n"
+    );
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::TestSite;
+use n4::{LinkProblem, MarkdownOptions, Site};
+use serde_json::json;
+
+#[test]
+fn good_links_are_clean() {
+    let test = TestSite::new();
+    test.write(
+        "docs/intro.md",
+        "# Intro\n\nSee [setup](../setup), [the api](/docs/api#calls), [docs](/docs) and [home](https://example.com/).\n",
+    );
+    test.write("docs/setup.md", "# Setup\n\n[Back](../intro?from=setup)\n");
+    test.write(
+        "docs/api.html",
+        "<h1 id=\"calls\">Calls</h1>\n<a href=\"../intro\">Intro</a>\n",
+    );
+
+    let report = test.site.check_links().unwrap();
+    assert!(report.is_clean(), "{}", report);
+    assert_eq!(report.checked, 5);
+}
+
+#[test]
+fn broken_links_report_file_and_line() {
+    let test = TestSite::new();
+    test.write(
+        "docs/intro.md",
+        "---\ntitle: Intro\n---\n# Intro\n\nFine [setup](../setup).\n\nGone [old](/docs/old).\n",
+    );
+    test.write("docs/setup.md", "# Setup\n");
+    test.write(
+        "docs/api.html",
+        "<h1>API</h1>\n<p><a href=\"../setup#nowhere\">x</a> <a href='../../../up'>y</a></p>\n",
+    );
+
+    let report = test.site.check_links().unwrap();
+    assert_eq!(report.broken.len(), 3, "{}", report);
+
+    let missing = report
+        .broken
+        .iter()
+        .find(|x| x.target == "/docs/old")
+        .unwrap();
+    assert_eq!(missing.file, test.path("docs/intro.md"));
+    assert_eq!(missing.line, 8);
+    assert_eq!(missing.problem, LinkProblem::MissingPage);
+
+    let anchor = report
+        .broken
+        .iter()
+        .find(|x| x.target == "../setup#nowhere")
+        .unwrap();
+    assert_eq!(anchor.file, test.path("docs/api.html"));
+    assert_eq!(anchor.line, 2);
+    assert_eq!(anchor.problem, LinkProblem::MissingAnchor);
+
+    let escape = report
+        .broken
+        .iter()
+        .find(|x| x.target == "../../../up")
+        .unwrap();
+    assert_eq!(escape.problem, LinkProblem::InvalidPath);
+
+    assert!(report
+        .to_string()
+        .contains("docs/intro.md:8: /docs/old (no such page)"));
+}
+
+#[test]
+fn anchors_come_from_rendered_headings() {
+    let test = TestSite::new();
+    let mut config = test.site.config().clone();
+    config.markdown = MarkdownOptions {
+        toc: true,
+        ..MarkdownOptions::default()
+    };
+    let site = Site::new(config);
+    test.write(
+        "guide.md",
+        "# Guide\n\n## Getting Started\n\n[Start](#getting-started) [Nope](#finishing)\n",
+    );
+
+    let report = site.check_links().unwrap();
+    assert_eq!(report.checked, 2);
+    assert_eq!(report.broken.len(), 1);
+    assert_eq!(report.broken[0].target, "#finishing");
+    assert_eq!(report.broken[0].line, 5);
+}
+
+#[test]
+fn content_list_entries_are_checked() {
+    let test = TestSite::new();
+    test.write("index.md", "# Home\n");
+    test.write("posts/first.md", "# First\n");
+    test.write_content_meta(
+        "index",
+        json!({ "content_list": ["/posts/first", "/posts/second"] }),
+    );
+
+    let report = test.site.check_links().unwrap();
+    assert_eq!(report.broken.len(), 1, "{}", report);
+    let broken = &report.broken[0];
+    assert_eq!(broken.target, "/posts/second");
+    assert_eq!(broken.file, test.path("index.content_meta"));
+    assert_eq!(broken.line, 1);
+}
+
+#[test]
+fn published_paths_and_index_pages_resolve() {
+    let test = TestSite::new();
+    let mut config = test.site.config().clone();
+    config.markdown = MarkdownOptions {
+        wiki_links: true,
+        toc: true,
+        ..MarkdownOptions::default()
+    };
+    let site = Site::new(config);
+    test.write_content_meta(
+        "docs/intro",
+        json!({ "title": "Intro", "path": "/guide/intro" }),
+    );
+    test.write("docs/intro.md", "# Intro\n\n## Install\n");
+    test.write("docs/setup.md", "# Setup\n");
+    test.write(
+        "docs/index.md",
+        "See [[Intro]], [x](/guide/intro#install), [setup](setup) and [up](../docs/).\n",
+    );
+    test.write("about.md", "[Missing](/guide/outro)\n");
+
+    let report = site.check_links().unwrap();
+    assert_eq!(report.checked, 5);
+    assert_eq!(report.broken.len(), 1, "{}", report);
+    assert_eq!(report.broken[0].target, "/guide/outro");
+}
+
+#[test]
+fn links_have_to_go_where_export_writes() {
+    let test = TestSite::new();
+    test.write("b.md", "---\npath: /blog/b\n---\n# B\n");
+    test.write("draft.md", "---\ndraft: true\n---\n# Draft\n");
+    test.write(
+        "index.md",
+        "[Moved](/blog/b) [Old](/b) [Draft](/draft) [Index](/index)\n",
+    );
+    test.write_content_meta("index", json!({ "content_list": ["/b"] }));
+
+    let report = test.site.check_links().unwrap();
+    assert_eq!(report.checked, 5);
+    let mut broken: Vec<&str> = report.broken.iter().map(|x| x.target.as_str()).collect();
+    broken.sort();
+    assert_eq!(broken, ["/b", "/draft", "/index"], "{}", report);
+}
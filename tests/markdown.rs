@@ -0,0 +1,130 @@
+mod common;
+
+use common::TestSite;
+use n4::{MarkdownOptions, Site};
+use serde_json::json;
+
+const TABLE: &str = "| a | b |\n|---|---|\n| 1 | 2 |\n\n~~gone~~\n";
+
+#[test]
+fn extensions_are_off_by_default() {
+    let test = TestSite::new();
+    test.write("page.md", TABLE);
+
+    let page = test.site.read_single_page("/page").unwrap();
+    assert!(!page.markdown.rendered_html().contains("<table>"));
+    assert!(!page.markdown.rendered_html().contains("<del>"));
+}
+
+#[test]
+fn site_section_and_page_options_layer() {
+    let test = TestSite::new();
+    let mut config = test.site.config().clone();
+    config.markdown = MarkdownOptions {
+        table: true,
+        header_ids: Some(String::new()),
+        ..MarkdownOptions::default()
+    };
+    let site = Site::new(config);
+    test.write("page.md", TABLE);
+    test.write_menu_meta("plain", json!({ "markdown": { "table": false } }));
+    test.write("plain/page.md", TABLE);
+    test.write_content_meta(
+        "plain/struck",
+        json!({ "path": "/plain/struck", "markdown": { "strikethrough": true } }),
+    );
+    test.write("plain/struck.md", TABLE);
+    test.write("titled.md", "# A Title\n");
+
+    let page = site.read_single_page("/page").unwrap();
+    assert!(page.markdown.rendered_html().contains("<table>"));
+    let plain = site.read_single_page("/plain/page").unwrap();
+    assert!(!plain.markdown.rendered_html().contains("<table>"));
+    let struck = site.read_single_page("/plain/struck").unwrap();
+    assert!(!struck.markdown.rendered_html().contains("<table>"));
+    assert!(struck.markdown.rendered_html().contains("<del>gone</del>"));
+    let titled = site.read_single_page("/titled").unwrap();
+    assert!(titled.markdown.rendered_html().contains("id=\"a-title\""));
+}
+
+#[test]
+fn source_is_kept_and_rendering_is_lazy() {
+    let test = TestSite::new();
+    test.write("page.md", "---\ntitle: Page\n---\n~~old~~ text\n");
+
+    let mut page = test.site.read_single_page("/page").unwrap();
+    assert_eq!(page.markdown.source, "~~old~~ text\n");
+    assert!(page.markdown.rendered.is_none());
+
+    assert_eq!(page.markdown.render(), "<p>~~old~~ text</p>\n");
+    assert!(page.markdown.rendered.is_some());
+    let options = MarkdownOptions {
+        strikethrough: true,
+        ..MarkdownOptions::default()
+    };
+    assert_eq!(
+        page.markdown.rerender(&options),
+        "<p><del>old</del> text</p>\n"
+    );
+    assert_eq!(
+        n4::render_markdown("*hi*", &MarkdownOptions::default()),
+        "<p><em>hi</em></p>\n"
+    );
+}
+
+#[test]
+fn toc_lists_headings_and_anchors_the_rendered_ones() {
+    let test = TestSite::new();
+    test.write_content_meta(
+        "doc",
+        json!({ "path": "/doc", "markdown": { "toc": true } }),
+    );
+    test.write(
+        "doc.md",
+        "# Getting `n4` Started\n\n## Setup\n\ntext\n\n## Setup\n\nMore\n----\n",
+    );
+    test.write("plain.md", "# No TOC\n");
+
+    let page = test.site.read_single_page("/doc").unwrap();
+    let toc: Vec<(u32, &str, &str)> = page
+        .toc
+        .iter()
+        .map(|x| (x.level, x.text.as_str(), x.anchor.as_str()))
+        .collect();
+    assert_eq!(
+        toc,
+        vec![
+            (1, "Getting n4 Started", "getting-n4-started"),
+            (2, "Setup", "setup"),
+            (2, "Setup", "setup-1"),
+            (2, "More", "more"),
+        ]
+    );
+    let rendered = page.markdown.rendered_html();
+    assert!(rendered.contains("<h1 id=\"getting-n4-started\">Getting <code>n4</code> Started</h1>"));
+    assert!(rendered.contains("<h2 id=\"setup-1\">Setup</h2>"));
+    assert!(!rendered.contains('\u{E000}'));
+
+    let plain = test.site.read_single_page("/plain").unwrap();
+    assert!(plain.toc.is_empty());
+    assert_eq!(plain.markdown.rendered_html(), "<h1>No TOC</h1>\n");
+}
+
+#[test]
+fn fenced_code_is_highlighted_with_classes() {
+    let test = TestSite::new();
+    test.write_menu_meta("code", json!({ "markdown": { "highlight": true } }));
+    test.write("code/page.md", "```rust\nfn main() {}\n```\n");
+    test.write("plain.md", "```rust\nfn main() {}\n```\n");
+
+    let page = test.site.read_single_page("/code/page").unwrap();
+    let rendered = page.markdown.rendered_html();
+    assert!(rendered.contains("<pre class=\"syntax-highlighting\">"));
+    assert!(rendered.contains("<span class=\"source rust\">"));
+    let plain = test.site.read_single_page("/plain").unwrap();
+    assert!(!plain.markdown.rendered_html().contains("<span"));
+
+    let css = test.site.highlight_css().unwrap();
+    assert!(css.contains(".source"));
+    assert!(n4::highlight_css("No Such Theme").is_err());
+}
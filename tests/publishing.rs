@@ -0,0 +1,128 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::TestSite;
+use n4::{ContentMeta, Site};
+
+fn publishing_site() -> TestSite {
+    let test = TestSite::new();
+    let tomorrow = (Utc::now() + Duration::days(1)).format("%Y-%m-%d");
+    test.write(
+        "news/live.md",
+        "---\ntitle: Live\nweight: 1\npublished: 2024-01-01\nupdated: 2024-05-06\n---\nLive\n",
+    );
+    test.write(
+        "news/draft.md",
+        "---\ntitle: Draft\ndraft: true\n---\nDraft\n",
+    );
+    test.write(
+        "news/scheduled.md",
+        &format!(
+            "---\ntitle: Scheduled\npublished: {}\n---\nSoon\n",
+            tomorrow
+        ),
+    );
+    test.write(
+        "news/expired.md",
+        "---\ntitle: Expired\nexpires: 2024-02-01\n---\nGone\n",
+    );
+    test.write(
+        "news/index.md",
+        "---\ncontent_list: [/news/live, /news/draft, /news/scheduled, /news/expired]\n---\n# News\n",
+    );
+    test.write("wip/index.md", "---\ndraft: true\n---\n# Not yet\n");
+    test.write("wip/page.md", "---\ntitle: Page\n---\nPage\n");
+    test
+}
+
+fn preview(test: &TestSite) -> Site {
+    let mut config = test.site.config().clone();
+    config.preview = true;
+    Site::new(config)
+}
+
+#[test]
+fn live_pages_are_in_their_window() {
+    let now = Utc::now();
+    let mut meta = ContentMeta::default();
+    assert!(meta.is_live_at(now));
+    meta.draft = true;
+    assert!(!meta.is_live_at(now));
+
+    let mut meta = ContentMeta::default();
+    meta.published = Some(now + Duration::hours(1));
+    assert!(!meta.is_live_at(now));
+    assert!(meta.is_live_at(now + Duration::hours(1)));
+
+    let mut meta = ContentMeta::default();
+    meta.expires = Some(now);
+    assert!(!meta.is_live_at(now));
+    assert!(meta.is_live_at(now - Duration::seconds(1)));
+}
+
+#[test]
+fn listings_leave_out_unpublished_pages() {
+    let test = publishing_site();
+    let titles: Vec<String> = test
+        .site
+        .read_full_dir_sorted("/news")
+        .unwrap()
+        .into_iter()
+        .map(|x| x.title)
+        .collect();
+    assert_eq!(titles, ["Live", "index"]);
+
+    let index = test.site.read_single_page("/news/index").unwrap();
+    let listed: Vec<&str> = index.list.iter().map(|x| x.meta.title.as_str()).collect();
+    assert_eq!(listed, ["Live"]);
+
+    // Still there for anyone with the link
+    assert!(test.site.read_single_page("/news/draft").is_ok());
+}
+
+#[test]
+fn sitemap_and_menus_leave_out_unpublished_pages() {
+    let test = publishing_site();
+    let sitemap = test.site.generate_sitemap().unwrap();
+    let mut locations: Vec<&str> = sitemap.iter().map(|x| x.location.as_str()).collect();
+    locations.sort();
+    // A draft section index only hides the section's menu, the pages under it go by their own metas
+    assert_eq!(
+        locations,
+        [
+            "https://example.com/news/",
+            "https://example.com/news/live",
+            "https://example.com/wip/page"
+        ]
+    );
+    let live = sitemap
+        .iter()
+        .find(|x| x.location.ends_with("/live"))
+        .unwrap();
+    assert_eq!(live.lastmod.to_rfc3339(), "2024-05-06T00:00:00+00:00");
+
+    let menus = test
+        .site
+        .tree_to_menus(test.site.generate_content_state().unwrap())
+        .unwrap();
+    assert!(menus.contains_key("news"));
+    assert!(!menus.contains_key("wip"));
+
+    let out = tempfile::tempdir().unwrap();
+    test.site.export_site(out.path()).unwrap();
+    assert!(out.path().join("wip/page/index.html").exists());
+    assert!(!out.path().join("wip/index.html").exists());
+}
+
+#[test]
+fn preview_lists_everything() {
+    let test = publishing_site();
+    let site = preview(&test);
+    assert_eq!(site.read_full_dir_sorted("/news").unwrap().len(), 5);
+    assert_eq!(site.read_single_page("/news/index").unwrap().list.len(), 4);
+    assert_eq!(site.generate_sitemap().unwrap().len(), 7);
+    let menus = site
+        .tree_to_menus(site.generate_content_state().unwrap())
+        .unwrap();
+    assert!(menus.contains_key("wip"));
+}
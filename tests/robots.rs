@@ -0,0 +1,72 @@
+mod common;
+
+use common::TestSite;
+use n4::{RobotsConfig, RobotsGroup, Site};
+use serde_json::json;
+
+#[test]
+fn default_robots_allows_everything_and_points_at_the_sitemap() {
+    let test = TestSite::new();
+    test.write("about.md", "# About");
+
+    let robots = test.site.generate_robot_food().unwrap();
+    assert_eq!(
+        robots,
+        "User-agent: *\nAllow: /\n\nSitemap: https://example.com/sitemap.xml\n"
+    );
+}
+
+#[test]
+fn configured_groups_and_noindex_pages() {
+    let test = TestSite::new();
+    test.write("about.md", "# About");
+    test.write_content_meta(
+        "secret",
+        json!({ "noindex": true, "path": "/private/secret" }),
+    );
+    test.write_content_meta("docs/index", json!({ "noindex": true, "path": "" }));
+    test.write("docs/index.md", "# Docs");
+    test.write("secret.md", "# Secret");
+    let mut config = test.site.config().clone();
+    config.robots = RobotsConfig {
+        groups: vec![
+            RobotsGroup {
+                disallow: vec![String::from("/tmp")],
+                ..RobotsGroup::default()
+            },
+            RobotsGroup {
+                user_agents: vec![String::from("SlowBot"), String::from("OtherBot")],
+                allow: Vec::new(),
+                disallow: Vec::new(),
+                crawl_delay: Some(10),
+            },
+        ],
+        sitemaps: vec![
+            String::from("https://example.com/sitemap.xml"),
+            String::from("https://example.com/news.xml"),
+        ],
+        disallow_noindex: true,
+    };
+    let site = Site::new(config);
+
+    let robots = site.generate_robot_food().unwrap();
+    assert_eq!(
+        robots,
+        "User-agent: *\nAllow: /\nDisallow: /tmp\nDisallow: /docs/$\nDisallow: /private/secret$\n\n\
+         User-agent: SlowBot\nUser-agent: OtherBot\nDisallow: /docs/$\nDisallow: /private/secret$\nCrawl-delay: 10\n\n\
+         Sitemap: https://example.com/sitemap.xml\nSitemap: https://example.com/news.xml\n"
+    );
+    let sitemap = site.generate_sitemap().unwrap();
+    assert_eq!(sitemap.len(), 1);
+}
+
+#[test]
+fn noindex_entries_are_encoded_and_nothing_is_written() {
+    let test = TestSite::new();
+    test.write("plain.md", "# No meta");
+    test.write("c# notes.md", "---\nnoindex: true\n---\n# Notes\n");
+
+    let robots = test.site.generate_robot_food().unwrap();
+    assert!(robots.contains("Disallow: /c%23%20notes$\n"));
+    assert!(!test.path("plain.content_meta").exists());
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::TestSite;
+use n4::{MarkdownOptions, SanitizeConfig, Site};
+use serde_json::json;
+
+const DIRTY: &str =
+    "<p onclick=\"steal()\">Hi <a href=\"javascript:steal()\">x</a></p><script>steal()</script>";
+
+#[test]
+fn html_and_markdown_bodies_are_cleaned() {
+    let test = TestSite::new();
+    let mut config = test.site.config().clone();
+    config.markdown = MarkdownOptions {
+        unsafe_html: true,
+        ..MarkdownOptions::default()
+    };
+    let site = Site::new(config);
+    test.write("page.html", DIRTY);
+    test.write("post.md", &format!("# Post\n\n{}\n", DIRTY));
+
+    let page = site.read_single_page("/page").unwrap();
+    let body = page.html.unwrap().body;
+    assert!(!body.contains("script"));
+    assert!(!body.contains("onclick"));
+    assert!(!body.contains("javascript:"));
+    assert!(body.contains("<p>Hi "));
+
+    let post = site.read_single_page("/post").unwrap();
+    let rendered = post.markdown.rendered_html();
+    assert!(rendered.contains("<h1>Post</h1>"));
+    assert!(!rendered.contains("script"));
+}
+
+#[test]
+fn trusted_sections_and_custom_allowlists() {
+    let test = TestSite::new();
+    test.write_menu_meta("embeds", json!({ "trusted": true }));
+    test.write("embeds/page.html", DIRTY);
+    test.write(
+        "video.html",
+        "<iframe src=\"https://example.org/v\"></iframe><b>b</b>",
+    );
+
+    let trusted = test.site.read_single_page("/embeds/page").unwrap();
+    assert_eq!(trusted.html.unwrap().body, DIRTY);
+
+    let mut config = test.site.config().clone();
+    let mut sanitize = SanitizeConfig {
+        tags: vec![String::from("iframe")],
+        ..SanitizeConfig::default()
+    };
+    sanitize
+        .attributes
+        .insert(String::from("iframe"), vec![String::from("src")]);
+    config.sanitize = sanitize;
+    let site = Site::new(config);
+    let video = site.read_single_page("/video").unwrap();
+    assert_eq!(
+        video.html.unwrap().body,
+        "<iframe src=\"https://example.org/v\"></iframe>b"
+    );
+}
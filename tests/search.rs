@@ -0,0 +1,100 @@
+mod common;
+
+use common::TestSite;
+use n4::{ContentIndex, SearchIndex};
+use serde_json::{json, Value};
+
+fn docs_site() -> TestSite {
+    let test = TestSite::new();
+    test.write(
+        "docs/install.md",
+        "# Install\n\nDownload the binary and put it on your path. Installing from source needs cargo.\n",
+    );
+    test.write_content_meta(
+        "docs/install",
+        json!({ "title": "Install n4", "description": "Getting n4 onto a machine", "path": "/docs/install" }),
+    );
+    test.write(
+        "docs/templates.html",
+        "<h1>Templates</h1><p>Tera templates render every page, see the install guide first.</p>",
+    );
+    test.write_content_meta(
+        "docs/templates",
+        json!({ "title": "Templates", "description": "", "path": "/docs/templates" }),
+    );
+    test.write("secret.md", "# Secret\n\nInstall the hidden thing.\n");
+    test.write_content_meta("secret", json!({ "noindex": true }));
+    test
+}
+
+#[test]
+fn queries_rank_title_matches_first() {
+    let test = docs_site();
+    let index = test.site.search_index().unwrap();
+    assert_eq!(index.len(), 2);
+
+    let hits = index.search("install", 10);
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].location, "/docs/install");
+    assert_eq!(hits[0].title, "Install n4");
+    assert_eq!(hits[1].location, "/docs/templates");
+    assert!(hits[0].score > hits[1].score);
+    assert!(hits[1].snippet.contains("see the install guide first."));
+
+    assert_eq!(index.search("tera", 10)[0].location, "/docs/templates");
+    assert!(index.search("nothing matches this", 10).is_empty());
+    assert_eq!(index.search("install", 1).len(), 1);
+}
+
+#[test]
+fn snippets_are_cut_around_the_match() {
+    let test = TestSite::new();
+    let filler = "word ".repeat(50);
+    test.write(
+        "long.md",
+        &format!("{}needle in the haystack {}", filler, filler),
+    );
+
+    let hits = test.site.search_index().unwrap().search("needle", 10);
+    let snippet = &hits[0].snippet;
+    assert!(snippet.starts_with("… word"));
+    assert!(snippet.ends_with(" …"));
+    assert!(snippet.contains("needle in the haystack"));
+}
+
+#[test]
+fn saved_indexes_load_back() {
+    let test = docs_site();
+    let index = test.site.search_index().unwrap();
+    let file = test.dir.path().join("search.json");
+    index.save(&file).unwrap();
+    assert_eq!(SearchIndex::load(&file).unwrap(), index);
+
+    std::fs::write(&file, "{ not json").unwrap();
+    assert!(SearchIndex::load(&file).is_err());
+}
+
+#[test]
+fn content_index_searches_its_cache() {
+    let test = docs_site();
+    let index = ContentIndex::build(test.site.clone()).unwrap();
+    assert_eq!(index.search_index(), test.site.search_index().unwrap());
+}
+
+#[test]
+fn compact_export_has_documents_and_postings() {
+    let test = docs_site();
+    let out = tempfile::tempdir().unwrap();
+    test.site.export_site(out.path()).unwrap();
+
+    let compact: Value = serde_json::from_str(
+        &std::fs::read_to_string(out.path().join("search_index.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(compact["documents"][0][0], "/docs/install");
+    assert_eq!(compact["documents"][1][1], "Templates");
+    let tera = compact["terms"]["tera"].as_array().unwrap();
+    // Document 1, once in the body
+    assert_eq!(tera, &vec![json!(1), json!(1)]);
+    assert!(compact.get("text").is_none());
+}
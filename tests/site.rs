@@ -0,0 +1,130 @@
+mod common;
+
+use common::TestSite;
+
+#[test]
+fn reads_a_markdown_page() {
+    let test = TestSite::new();
+    test.write("hello.md", "# Hello\n\nWorld");
+
+    let page = test.site.read_single_page("/hello").unwrap();
+    assert!(page.markdown.rendered_html().contains("<h1>Hello</h1>"));
+    assert_eq!(page.meta.path, "/hello");
+    assert!(page.html.is_none());
+}
+
+#[test]
+fn missing_page_is_not_found() {
+    let test = TestSite::new();
+
+    let err = test.site.read_single_page("/nope").unwrap_err();
+    assert!(err.is_not_found());
+}
+
+#[test]
+fn section_meta_comes_from_the_parent_directory() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "Intro");
+    test.write_menu_meta("docs", serde_json::json!({ "description": "Docs section" }));
+
+    let page = test.site.read_single_page("/docs/intro").unwrap();
+    assert_eq!(page.section_meta.description, "Docs section");
+}
+
+#[test]
+fn sites_do_not_share_content() {
+    let first = TestSite::new();
+    let second = TestSite::new();
+    first.write("only_here.md", "First");
+
+    assert!(first.site.does_content_exist("/only_here"));
+    assert!(!second.site.does_content_exist("/only_here"));
+}
+
+#[test]
+fn read_only_mode_never_writes_meta() {
+    let test = TestSite::new();
+    test.write("hello.md", "# Hello");
+    let mut config = test.site.config().clone();
+    config.read_only = true;
+    let site = n4::Site::new(config);
+
+    let page = site.read_single_page("/hello").unwrap();
+    assert_eq!(page.meta.path, "/hello");
+    site.generate_sitemap().unwrap();
+    assert!(!test.path("hello.content_meta").exists());
+}
+
+#[test]
+fn scaffold_missing_meta_reports_what_it_wrote() {
+    let test = TestSite::new();
+    test.write("hello.md", "# Hello");
+    test.write("docs/intro.html", "<p>Intro</p>");
+    test.write("fronted.md", "---\ntitle: Has front matter\n---\nBody");
+    test.write_content_meta("done", serde_json::json!({ "path": "/done" }));
+    test.write("done.md", "Done");
+
+    let written = test.site.scaffold_missing_meta().unwrap();
+    assert_eq!(
+        written,
+        vec![
+            test.path("docs/intro.content_meta"),
+            test.path("hello.content_meta")
+        ]
+    );
+    assert!(test.site.scaffold_missing_meta().unwrap().is_empty());
+}
+
+#[test]
+fn pages_without_markdown_render_nothing_for_it() {
+    let test = TestSite::new();
+    test.write("data.json", "{\"a\": 1}");
+    let mut page = test.site.read_single_page("/data").unwrap();
+    assert_eq!(page.markdown.render(), "");
+}
+
+#[test]
+fn a_bad_meta_only_costs_its_own_entry() {
+    let test = TestSite::new();
+    test.write("docs/good.md", "# Good");
+    test.write("docs/bad.md", "# Bad");
+    test.write("docs/bad.content_meta", "{ not json");
+    test.write("docs/guide/intro.md", "# Intro");
+    test.write("docs/guide.menu_meta", "{ not json");
+
+    let titles: Vec<String> = test
+        .site
+        .read_full_dir_sorted("/docs")
+        .unwrap()
+        .into_iter()
+        .map(|x| x.title)
+        .collect();
+    assert_eq!(titles, ["good"]);
+
+    let menus = test
+        .site
+        .tree_to_menus(test.site.generate_content_state().unwrap())
+        .unwrap();
+    // The broken section falls back to the defaults instead of failing every menu
+    let menus = serde_json::to_value(&menus).unwrap();
+    assert_eq!(
+        menus["docs"]["children"]["guide"]["menu_meta"]["description"],
+        "Menu default description."
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn a_dangling_symlink_only_costs_its_own_entry() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "# Intro");
+    std::os::unix::fs::symlink(test.path("nowhere.md"), test.path("docs/gone.md")).unwrap();
+
+    let tree = test.site.generate_content_state().unwrap();
+    assert!(!tree.directories["docs"].files.contains_key("gone"));
+    assert_eq!(test.site.generate_sitemap().unwrap().len(), 1);
+    assert!(test.site.tree_to_menus(tree).unwrap().contains_key("docs"));
+    let out = tempfile::tempdir().unwrap();
+    assert!(test.site.export_site(out.path()).unwrap().is_success());
+    assert!(n4::ContentIndex::build(test.site.clone()).is_ok());
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use std::io::Read;
+
+use chrono::{TimeZone, Utc};
+use common::TestSite;
+use flate2::read::GzDecoder;
+use n4::{render_sitemap_xml, render_sitemaps, ChangeFreq, N4Error, SiteMapEntry, SitemapOptions};
+use serde_json::json;
+
+fn entry(location: &str) -> SiteMapEntry {
+    SiteMapEntry {
+        location: format!("https://example.com{}", location),
+        lastmod: Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap(),
+        priority: String::from("0.64"),
+        changefreq: None,
+    }
+}
+
+#[test]
+fn urlset_is_escaped_and_changefreq_is_optional() {
+    let mut weekly = entry("/a&b");
+    weekly.changefreq = Some(ChangeFreq::Weekly);
+    let xml = render_sitemap_xml(&[weekly, entry("/plain")]);
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(xml.contains("<loc>https://example.com/a&amp;b</loc>"));
+    assert!(xml.contains("<lastmod>2020-01-02T03:04:05+00:00</lastmod>"));
+    assert_eq!(xml.matches("<changefreq>weekly</changefreq>").count(), 1);
+    assert_eq!(xml.matches("<url>").count(), 2);
+}
+
+#[test]
+fn too_many_urls_split_into_an_index() {
+    let entries: Vec<SiteMapEntry> = (0..5).map(|x| entry(&format!("/{}", x))).collect();
+    let options = SitemapOptions {
+        max_urls: 2,
+        ..SitemapOptions::default()
+    };
+    let files = render_sitemaps(&entries, "https://example.com", &options).unwrap();
+
+    let names: Vec<&str> = files.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "sitemap-1.xml",
+            "sitemap-2.xml",
+            "sitemap-3.xml",
+            "sitemap.xml"
+        ]
+    );
+    let index = String::from_utf8(files[3].contents.clone()).unwrap();
+    assert!(index.contains("<sitemapindex"));
+    assert!(index.contains("<loc>https://example.com/sitemap-3.xml</loc>"));
+    let last = String::from_utf8(files[2].contents.clone()).unwrap();
+    assert_eq!(last.matches("<url>").count(), 1);
+}
+
+#[test]
+fn gzip_output_decompresses_to_the_urlset() {
+    let options = SitemapOptions {
+        gzip: true,
+        ..SitemapOptions::default()
+    };
+    let files = render_sitemaps(&[entry("/one")], "https://example.com", &options).unwrap();
+
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].name, "sitemap.xml.gz");
+    let mut xml = String::new();
+    GzDecoder::new(files[0].contents.as_slice())
+        .read_to_string(&mut xml)
+        .unwrap();
+    assert!(xml.contains("<loc>https://example.com/one</loc>"));
+}
+
+#[test]
+fn site_sitemap_has_one_entry_per_page() {
+    let test = TestSite::new();
+    test.write("about.md", "# About");
+    test.write("about.html", "<p>About</p>");
+    test.write("about.json", "{}");
+    test.write(".draft.md", "# Hidden");
+    test.write(".private/notes.md", "# Hidden");
+    test.write_content_meta("docs/intro", json!({ "path": "/guide/intro" }));
+    test.write("docs/intro.md", "# Intro");
+
+    let mut locations: Vec<String> = test
+        .site
+        .generate_sitemap()
+        .unwrap()
+        .into_iter()
+        .map(|x| x.location)
+        .collect();
+    locations.sort();
+    assert_eq!(
+        locations,
+        vec![
+            "https://example.com/about",
+            "https://example.com/guide/intro"
+        ]
+    );
+    // Nothing gets written into the content tree along the way
+    assert!(!test.path("about.content_meta").exists());
+}
+
+#[test]
+fn page_and_section_sitemap_values_are_inherited() {
+    let test = TestSite::new();
+    test.write_menu_meta(
+        "blog",
+        json!({ "sitemap_priority": 0.8, "sitemap_changefreq": "daily" }),
+    );
+    test.write_menu_meta("blog/drafts", json!({ "sitemap_exclude": true }));
+    test.write("blog/2020/post.md", "# Post");
+    test.write("blog/drafts/soon.md", "# Soon");
+    test.write_content_meta(
+        "blog/pinned",
+        json!({ "path": "/blog/pinned", "sitemap_priority": 1.0 }),
+    );
+    test.write("blog/pinned.md", "# Pinned");
+    test.write("about.md", "# About");
+
+    let mut entries = test.site.generate_sitemap().unwrap();
+    entries.sort_by(|a, b| a.location.cmp(&b.location));
+    let found: Vec<(&str, &str, Option<ChangeFreq>)> = entries
+        .iter()
+        .map(|x| (x.location.as_str(), x.priority.as_str(), x.changefreq))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("https://example.com/about", "0.64", None),
+            (
+                "https://example.com/blog/2020/post",
+                "0.8",
+                Some(ChangeFreq::Daily)
+            ),
+            (
+                "https://example.com/blog/pinned",
+                "1",
+                Some(ChangeFreq::Daily)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn out_of_range_priority_is_a_meta_error() {
+    let test = TestSite::new();
+    test.write_content_meta("page", json!({ "sitemap_priority": 1.5 }));
+    test.write("page.md", "# Page");
+
+    match test.site.read_single_page("/page") {
+        Err(N4Error::MetaParse { .. }) => {}
+        other => panic!("expected a MetaParse error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn locations_are_percent_encoded_under_a_trimmed_host() {
+    let test = TestSite::new();
+    test.write("my page.md", "# Spaced");
+    test.write("café.md", "# Accented");
+    let mut config = test.site.config().clone();
+    config.prod_host = String::from("https://example.com/");
+    let site = n4::Site::new(config);
+
+    let xml = render_sitemap_xml(&site.generate_sitemap().unwrap());
+    assert!(xml.contains("<loc>https://example.com/my%20page</loc>"));
+    assert!(xml.contains("<loc>https://example.com/caf%C3%A9</loc>"));
+    assert!(!xml.contains("example.com//"));
+}
+
+#[test]
+fn reserved_characters_in_a_segment_are_encoded() {
+    let test = TestSite::new();
+    test.write("c#.md", "# Sharp");
+    test.write("q?.md", "# Question");
+    test.write("100%.md", "# Percent");
+    test.write("a@[b].md", "# Brackets");
+
+    let xml = render_sitemap_xml(&test.site.generate_sitemap().unwrap());
+    assert!(xml.contains("<loc>https://example.com/c%23</loc>"));
+    assert!(xml.contains("<loc>https://example.com/q%3F</loc>"));
+    assert!(xml.contains("<loc>https://example.com/100%25</loc>"));
+    assert!(xml.contains("<loc>https://example.com/a%40%5Bb%5D</loc>"));
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use common::TestSite;
+use n4::{ContentIndex, TaxonomyKind, TermSort};
+use serde_json::json;
+
+fn set_modified(test: &TestSite, relative: &str, seconds: u64) {
+    let file = std::fs::File::options()
+        .write(true)
+        .open(test.path(relative))
+        .unwrap();
+    file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+        .unwrap();
+}
+
+fn tagged_site() -> TestSite {
+    let test = TestSite::new();
+    test.write(
+        "posts/first.md",
+        "---\ntitle: First\nweight: 30\ntags: [Rust, web]\ncategories: [Notes]\n---\n# First\n",
+    );
+    test.write(
+        "posts/second.md",
+        "---\ntitle: Second\nweight: 10\ntags: [rust]\n---\n# Second\n",
+    );
+    test.write("posts/third.html", "<h1>Third</h1>");
+    test.write_content_meta(
+        "posts/third",
+        json!({ "title": "Third", "weight": 20, "tags": [" RUST ", "", "Web", "web"] }),
+    );
+    set_modified(&test, "posts/first.md", 3_000);
+    set_modified(&test, "posts/second.md", 1_000);
+    set_modified(&test, "posts/third.html", 2_000);
+    test
+}
+
+#[test]
+fn terms_are_collected_by_slug() {
+    let test = tagged_site();
+    let taxonomy = test.site.taxonomy().unwrap();
+
+    let rust = taxonomy.term(TaxonomyKind::Tags, "RUST").unwrap();
+    assert_eq!(rust.name, "Rust");
+    assert_eq!(rust.slug, "rust");
+    assert_eq!(rust.pages.len(), 3);
+    assert_eq!(
+        taxonomy
+            .term(TaxonomyKind::Tags, "web")
+            .unwrap()
+            .pages
+            .len(),
+        2
+    );
+    assert_eq!(taxonomy.tags.len(), 2);
+    let notes = taxonomy.term(TaxonomyKind::Categories, "notes").unwrap();
+    assert_eq!(notes.pages[0].web_path.to_string(), "/posts/first");
+    assert!(taxonomy.term(TaxonomyKind::Categories, "rust").is_none());
+}
+
+#[test]
+fn term_pages_sort_by_weight_or_date() {
+    let test = tagged_site();
+    let titles = |sort: TermSort| -> Vec<String> {
+        test.site
+            .term_pages(TaxonomyKind::Tags, "rust", sort)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.meta.title)
+            .collect()
+    };
+    assert_eq!(titles(TermSort::Weight), ["Second", "Third", "First"]);
+    assert_eq!(titles(TermSort::Date), ["First", "Third", "Second"]);
+    assert!(test
+        .site
+        .term_pages(TaxonomyKind::Tags, "missing", TermSort::Weight)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn cloud_levels_follow_counts() {
+    let test = tagged_site();
+    let cloud = test.site.taxonomy().unwrap().cloud(TaxonomyKind::Tags);
+    assert_eq!(cloud.len(), 2);
+    assert_eq!(
+        (cloud[0].slug.as_str(), cloud[0].count, cloud[0].level),
+        ("rust", 3, 5)
+    );
+    assert_eq!(
+        (cloud[1].slug.as_str(), cloud[1].count, cloud[1].level),
+        ("web", 2, 1)
+    );
+}
+
+#[test]
+fn content_index_matches_the_site() {
+    let test = tagged_site();
+    let index = ContentIndex::build(test.site.clone()).unwrap();
+    assert_eq!(index.taxonomy(), test.site.taxonomy().unwrap());
+    let pages = index
+        .term_pages(TaxonomyKind::Categories, "Notes", TermSort::Date)
+        .unwrap();
+    assert_eq!(pages.len(), 1);
+}
+
+#[test]
+fn terms_with_colliding_slugs_stay_apart() {
+    let test = TestSite::new();
+    test.write("a.md", "---\ntags: [C++]\n---\n# A\n");
+    test.write("b.md", "---\ntags: [C#, c++]\n---\n# B\n");
+    test.write("c.md", "---\ntags: [C]\n---\n# C\n");
+    let taxonomy = test.site.taxonomy().unwrap();
+
+    assert_eq!(taxonomy.tags.len(), 3);
+    let slugs: Vec<(&str, &str, usize)> = ["C++", "C#", "c"]
+        .iter()
+        .map(|name| {
+            let term = taxonomy.term(TaxonomyKind::Tags, name).unwrap();
+            (term.name.as_str(), term.slug.as_str(), term.pages.len())
+        })
+        .collect();
+    assert_eq!(slugs, [("C++", "c", 2), ("C#", "c-2", 1), ("C", "c-3", 1)]);
+    assert_eq!(taxonomy.term(TaxonomyKind::Tags, "c-2").unwrap().name, "C#");
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::TestSite;
+use serde_json::json;
+
+fn with_templates(test: &mut TestSite) -> std::path::PathBuf {
+    let template_dir = test.dir.path().join("templates");
+    std::fs::create_dir_all(&template_dir).unwrap();
+    let mut config = test.site.config().clone();
+    config.template_dir = template_dir.to_string_lossy().to_string();
+    test.site = n4::Site::new(config);
+    template_dir
+}
+
+#[test]
+fn builtin_default_renders_page_and_includes() {
+    let test = TestSite::new();
+    test.write("hello.md", "# Hello");
+    test.write_content_meta(
+        "hello",
+        json!({ "title": "Hi & bye", "css_include": ["/static/page.css"] }),
+    );
+
+    let html = test.site.render_page("/hello").unwrap();
+    assert!(html.contains("<title>Hi &amp; bye</title>"));
+    assert!(html.contains("<h1>Hello</h1>"));
+    assert!(html.contains(r#"<link rel="stylesheet" href="/static/page.css">"#));
+    assert!(html.contains(r#"<body class="section basic-page">"#));
+}
+
+#[test]
+fn template_chain_is_override_then_section_then_default() {
+    let mut test = TestSite::new();
+    let template_dir = with_templates(&mut test);
+    std::fs::write(
+        template_dir.join("article.html"),
+        "article:{{ page.meta.title }}",
+    )
+    .unwrap();
+    std::fs::write(
+        template_dir.join("special.html"),
+        "special:{{ page.meta.title }}",
+    )
+    .unwrap();
+    test.write("docs/plain.md", "Plain");
+    test.write_content_meta("docs/plain", json!({ "title": "Plain" }));
+    test.write("docs/fancy.md", "Fancy");
+    test.write_content_meta(
+        "docs/fancy",
+        json!({ "title": "Fancy", "template_override": "special" }),
+    );
+    test.write("other/missing.md", "Missing");
+    test.write_menu_meta("other", json!({ "section_template": "nope" }));
+
+    assert_eq!(
+        test.site.render_page("/docs/plain").unwrap(),
+        "article:Plain"
+    );
+    assert_eq!(
+        test.site.render_page("/docs/fancy").unwrap(),
+        "special:Fancy"
+    );
+    assert!(test
+        .site
+        .render_page("/other/missing")
+        .unwrap()
+        .starts_with("<!DOCTYPE html>"));
+}
+
+#[test]
+fn templates_can_extend_the_builtin_default() {
+    let mut test = TestSite::new();
+    let template_dir = with_templates(&mut test);
+    std::fs::write(
+        template_dir.join("article.html"),
+        r#"{% extends "default.html" %}"#,
+    )
+    .unwrap();
+    test.write("docs/page.md", "Body");
+
+    assert!(test
+        .site
+        .render_page("/docs/page")
+        .unwrap()
+        .contains("<p>Body</p>"));
+}
+
+#[test]
+fn index_pages_use_their_section_override_and_class() {
+    let mut test = TestSite::new();
+    let template_dir = with_templates(&mut test);
+    std::fs::write(
+        template_dir.join("landing.html"),
+        "landing:{{ includes.body_class }}",
+    )
+    .unwrap();
+    std::fs::write(
+        template_dir.join("article.html"),
+        "article:{{ includes.body_class }}",
+    )
+    .unwrap();
+    test.write("docs/index.md", "Docs");
+    test.write("docs/intro.md", "Intro");
+    test.write_menu_meta(
+        "docs",
+        json!({ "template_override": "landing", "content_class": "docs-home" }),
+    );
+
+    assert_eq!(
+        test.site.render_page("/docs/index").unwrap(),
+        "landing:section docs-home basic-page"
+    );
+    assert_eq!(
+        test.site.render_page("/docs/intro").unwrap(),
+        "article:section basic-page"
+    );
+}
+
+#[test]
+fn section_includes_are_inherited_from_every_level() {
+    let mut test = TestSite::new();
+    let template_dir = with_templates(&mut test);
+    std::fs::write(
+        template_dir.join("article.html"),
+        "{{ includes.css | join(sep=\",\") }}",
+    )
+    .unwrap();
+    test.write("docs/guide/intro.md", "Intro");
+    test.write_menu_meta("docs", json!({ "section_css_include": ["/docs.css"] }));
+    test.write_menu_meta(
+        "docs/guide",
+        json!({ "section_css_include": ["/guide.css"] }),
+    );
+
+    assert_eq!(
+        test.site.render_page("/docs/guide/intro").unwrap(),
+        "/docs.css,/guide.css"
+    );
+}
+
+#[test]
+fn templates_are_loaded_once_per_site() {
+    let mut test = TestSite::new();
+    let template_dir = with_templates(&mut test);
+    std::fs::write(template_dir.join("article.html"), "first").unwrap();
+    test.write("docs/page.md", "Body");
+
+    assert_eq!(test.site.render_page("/docs/page").unwrap(), "first");
+    std::fs::write(template_dir.join("article.html"), "second").unwrap();
+    assert_eq!(test.site.render_page("/docs/page").unwrap(), "first");
+    let index = n4::ContentIndex::build(test.site.clone()).unwrap();
+    assert_eq!(index.render_page("/docs/page").unwrap(), "first");
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::TestSite;
+
+#[test]
+fn traversal_is_rejected() {
+    let test = TestSite::new();
+    std::fs::write(test.dir.path().join("secret.md"), "outside").unwrap();
+
+    let err = test.site.read_single_page("/../secret").unwrap_err();
+    assert!(matches!(err, n4::N4Error::PathEscape(_)));
+    assert!(!test.site.does_content_exist("/../secret"));
+    assert!(!test.site.does_directory_exist("/.."));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_root_are_rejected() {
+    let test = TestSite::new();
+    std::fs::write(test.dir.path().join("secret.md"), "outside").unwrap();
+    std::os::unix::fs::symlink(test.dir.path().join("secret.md"), test.path("link.md")).unwrap();
+
+    assert!(!test.site.does_content_exist("/link"));
+    assert!(test.site.read_single_page("/link").is_err());
+}
+
+#[test]
+fn content_list_entries_cannot_escape() {
+    let test = TestSite::new();
+    std::fs::write(test.dir.path().join("secret.md"), "outside").unwrap();
+    test.write("list.md", "List page");
+    test.write_content_meta(
+        "list",
+        serde_json::json!({ "content_list": ["/../secret"] }),
+    );
+
+    let page = test.site.read_single_page("/list").unwrap();
+    assert!(page.list.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn directory_listings_skip_symlinks_out_of_the_root() {
+    let test = TestSite::new();
+    std::fs::write(
+        test.dir.path().join("secret.md"),
+        "---\ntitle: Secret\n---\n",
+    )
+    .unwrap();
+    test.write("docs/intro.md", "# Intro");
+    std::os::unix::fs::symlink(test.dir.path().join("secret.md"), test.path("docs/link.md"))
+        .unwrap();
+
+    let metas = test.site.read_full_dir_sorted("/docs").unwrap();
+    assert_eq!(metas.len(), 1);
+    assert_eq!(metas[0].title, "intro");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_directories_loop_or_escape_are_not_walked() {
+    let test = TestSite::new();
+    test.write("docs/intro.md", "# Intro");
+    std::fs::create_dir_all(test.dir.path().join("outside")).unwrap();
+    std::fs::write(test.dir.path().join("outside/secret.md"), "# Secret").unwrap();
+    std::os::unix::fs::symlink(test.path(""), test.path("loop")).unwrap();
+    std::os::unix::fs::symlink(test.path("docs"), test.path("docs/again")).unwrap();
+    std::os::unix::fs::symlink(test.dir.path().join("outside"), test.path("outside")).unwrap();
+
+    let locations: Vec<String> = test
+        .site
+        .generate_sitemap()
+        .unwrap()
+        .into_iter()
+        .map(|x| x.location)
+        .collect();
+    assert_eq!(locations, ["https://example.com/docs/intro"]);
+    let out = tempfile::tempdir().unwrap();
+    assert!(test.site.export_site(out.path()).unwrap().is_success());
+    assert!(n4::ContentIndex::build(test.site.clone()).is_ok());
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use common::TestSite;
+use n4::{ContentEvent, ContentIndex, LinkProblem, MarkdownOptions, Site};
+use serde_json::json;
+
+fn wiki_site(test: &TestSite) -> Site {
+    let mut config = test.site.config().clone();
+    config.markdown = MarkdownOptions {
+        wiki_links: true,
+        ..MarkdownOptions::default()
+    };
+    Site::new(config)
+}
+
+#[test]
+fn titles_and_paths_become_links() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write(
+        "docs/setup.md",
+        "---\ntitle: Getting Started\n---\n# Setup\n",
+    );
+    test.write("docs/api.md", "# API\n");
+    test.write(
+        "docs/intro.md",
+        "See [[getting started]], [[/docs/api|the API]] and [[/docs/api#calls]].\n",
+    );
+
+    let rendered = site
+        .read_single_page("/docs/intro")
+        .unwrap()
+        .markdown
+        .rendered_html();
+    // The sanitizer adds its rel to every link
+    assert!(rendered
+        .contains("<a href=\"/docs/setup\" rel=\"noopener noreferrer\">getting started</a>"));
+    assert!(rendered.contains("<a href=\"/docs/api\" rel=\"noopener noreferrer\">the API</a>"));
+    assert!(rendered.contains("<a href=\"/docs/api#calls\""));
+}
+
+#[test]
+fn missing_pages_are_marked() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write("intro.md", "Later: [[Not Written Yet]]\n");
+
+    let rendered = site
+        .read_single_page("/intro")
+        .unwrap()
+        .markdown
+        .rendered_html();
+    assert!(rendered.contains(
+        "<span class=\"missing-page\" title=\"Missing page: Not Written Yet\">Not Written Yet</span>"
+    ));
+    assert!(!rendered.contains("<a "));
+}
+
+#[test]
+fn wiki_links_are_off_by_default() {
+    let test = TestSite::new();
+    test.write("setup.md", "# Setup\n");
+    test.write("intro.md", "[[/setup]]\n");
+
+    let rendered = test
+        .site
+        .read_single_page("/intro")
+        .unwrap()
+        .markdown
+        .rendered_html();
+    assert!(rendered.contains("[[/setup]]"));
+}
+
+#[test]
+fn content_index_relinks_when_titles_change() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write("intro.md", "[[Setup Guide]]\n");
+    test.write("setup.md", "# Setup\n");
+    let mut index = ContentIndex::build(site).unwrap();
+    let page = index.read_single_page("/intro").unwrap();
+    assert!(page.markdown.rendered_html().contains("missing-page"));
+
+    test.write_content_meta("setup", json!({ "title": "Setup Guide", "path": "/setup" }));
+    index
+        .apply(ContentEvent::Modified(test.path("setup.content_meta")))
+        .unwrap();
+    let page = index.read_single_page("/intro").unwrap();
+    assert!(page
+        .markdown
+        .rendered_html()
+        .contains("<a href=\"/setup\" rel=\"noopener noreferrer\">Setup Guide</a>"));
+}
+
+#[test]
+fn check_links_reports_missing_wiki_pages() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write("setup.md", "# Setup\n");
+    test.write("intro.md", "# Intro\n\n[[/setup]] then [[Nowhere]]\n");
+
+    let report = site.check_links().unwrap();
+    assert_eq!(report.broken.len(), 1, "{}", report);
+    assert_eq!(report.broken[0].target, "[[Nowhere]]");
+    assert_eq!(report.broken[0].line, 3);
+    assert_eq!(report.broken[0].problem, LinkProblem::MissingPage);
+}
+
+#[test]
+fn a_bad_meta_elsewhere_only_leaves_that_page_out() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write("setup.md", "---\ntitle: Setup\n---\n# Setup\n");
+    test.write("intro.md", "See [[Setup]] and [[Broken]].\n");
+    test.write("broken.md", "Broken\n");
+    test.write("broken.content_meta", "{ not json");
+
+    let rendered = site
+        .read_single_page("/intro")
+        .unwrap()
+        .markdown
+        .rendered_html();
+    assert!(rendered.contains("<a href=\"/setup\""));
+    assert!(rendered.contains("<span class=\"missing-page\""));
+
+    let report = site.check_links().unwrap();
+    assert_eq!(report.broken.len(), 1);
+    assert_eq!(report.broken[0].target, "[[Broken]]");
+}
+
+#[test]
+fn single_page_reads_share_an_index_until_the_tree_changes() {
+    let test = TestSite::new();
+    let mut config = wiki_site(&test).config().clone();
+    // Nothing written on read, so only the test changes the tree
+    config.read_only = true;
+    let site = Site::new(config);
+    test.write("intro.md", "See [[Setup]]\n");
+    test.write("other.md", "Also [[Setup]]\n");
+
+    let first = site.read_single_page("/intro").unwrap();
+    let second = site.read_single_page("/other").unwrap();
+    assert!(std::sync::Arc::ptr_eq(
+        first.markdown.wiki.as_ref().unwrap(),
+        second.markdown.wiki.as_ref().unwrap()
+    ));
+    assert!(second
+        .markdown
+        .rendered_html()
+        .contains("class=\"missing-page\""));
+
+    test.write("setup.md", "---\ntitle: Setup\n---\n# Setup\n");
+    let third = site.read_single_page("/other").unwrap();
+    assert!(third
+        .markdown
+        .rendered_html()
+        .contains("<a href=\"/setup\""));
+}
+
+#[test]
+fn building_the_index_writes_no_metas() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write("intro.md", "See [[Setup]]\n");
+    test.write("setup.md", "# Setup\n");
+
+    site.read_single_page("/intro").unwrap();
+    assert!(!test.path("setup.content_meta").exists());
+}
+
+#[test]
+fn unlisted_pages_are_missing() {
+    let test = TestSite::new();
+    let site = wiki_site(&test);
+    test.write(
+        "d.md",
+        "---\ntitle: Secret Draft\ndraft: true\n---\n# Draft\n",
+    );
+    test.write("intro.md", "See [[Secret Draft]] and [[/d]]\n");
+
+    let rendered = site
+        .read_single_page("/intro")
+        .unwrap()
+        .markdown
+        .rendered_html();
+    assert!(!rendered.contains("<a "));
+    assert_eq!(rendered.matches("class=\"missing-page\"").count(), 2);
+    let index = ContentIndex::build(site.clone()).unwrap();
+    let cached = index.read_single_page("/intro").unwrap();
+    assert!(!cached.markdown.rendered_html().contains("<a "));
+    assert_eq!(site.check_links().unwrap().broken.len(), 2);
+
+    let mut config = site.config().clone();
+    config.preview = true;
+    let preview = Site::new(config);
+    assert!(preview
+        .read_single_page("/intro")
+        .unwrap()
+        .markdown
+        .rendered_html()
+        .contains("<a href=\"/d\""));
+}